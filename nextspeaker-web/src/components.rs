@@ -43,9 +43,13 @@ pub fn ChooseButton(_props: &ChooseButtonProps) -> Html {
     )));
     let onclick = selected_dispatch.reduce_mut_callback(move |selected| {
         if !candidates.value.is_empty() {
-            let history = ignore_non_candidates(&candidates.value, &history.value);
+            let history = ignore_non_candidates(&candidates.value, &history.value)
+                .iter()
+                .map(|name| nextspeaker::history::Entry::new(name))
+                .collect::<Vec<_>>();
+            let options = nextspeaker::Options::with_halflife(history_halflife);
             let new_selection =
-                nextspeaker::choose(&candidates.value, &history, history_halflife).unwrap();
+                nextspeaker::choose(&candidates.value, &history, &options).unwrap();
             history_dispatch.reduce_mut(|h| h.value.push(new_selection.clone()));
             selected.value = new_selection;
         }
@@ -76,6 +80,7 @@ pub struct SimulationBarProps {
     pub candidate: String,
     pub count: u64,
     pub total: u64,
+    pub probability: Option<f64>,
 }
 
 #[styled_component]
@@ -84,15 +89,21 @@ pub fn SimulationBar(props: &SimulationBarProps) -> Html {
         candidate,
         count,
         total,
+        probability,
     } = props;
     let pct = 100.0 * (*count as f64 / *total as f64);
     let pct = format!("{pct}");
+    let exact = match probability {
+        Some(p) => format!("{:.2}%", 100.0 * p),
+        None => "-".to_string(),
+    };
     html! {
         <tr key={candidate.clone()}>
             <td>{candidate}</td>
             <td>
                 <progress class="progress" value={pct} max={"100"}>{*count}</progress>
             </td>
+            <td>{exact}</td>
         </tr>
     }
 }
@@ -103,10 +114,19 @@ pub struct SimulationResultsProps {}
 #[styled_component]
 pub fn SimulationResults(_props: &SimulationResultsProps) -> Html {
     let (results, _) = use_store::<state::SimulationResults>();
+    let (probabilities, _) = use_store::<state::Probabilities>();
+    let probability = |candidate: &str| {
+        probabilities.value.as_ref().and_then(|probabilities| {
+            probabilities
+                .iter()
+                .find(|(name, _)| name == candidate)
+                .map(|(_, p)| *p)
+        })
+    };
     if let Some(results) = &results.value {
         html! {
             <table class={"is-striped"}>
-                <tr><th>{"candidate"}</th><th>{"selection count"}</th></tr>
+                <tr><th>{"candidate"}</th><th>{"selection count"}</th><th>{"exact chance"}</th></tr>
                 {
                     results.iter().map(|(candidate, count)| {
                         html! {
@@ -114,6 +134,7 @@ pub fn SimulationResults(_props: &SimulationResultsProps) -> Html {
                                 candidate={candidate.clone()}
                                 count={*count}
                                 total={N_SIM}
+                                probability={probability(candidate)}
                             />
                         }
                     }).collect::<Html>()
@@ -237,6 +258,24 @@ pub fn ModeSelect(_props: &ModeSelectProps) -> Html {
             let results = simulate::run(&candidates.value, &history.value, history_halflife);
             Dispatch::<state::SimulationResults>::new()
                 .set(state::SimulationResults { value: results });
+            let entries = ignore_non_candidates(&candidates.value, &history.value)
+                .iter()
+                .map(|name| nextspeaker::history::Entry::new(name))
+                .collect::<Vec<_>>();
+            let options = nextspeaker::Options::with_halflife(history_halflife);
+            let probabilities = nextspeaker::probabilities(&candidates.value, &entries, &options)
+                .ok()
+                .map(|probabilities| {
+                    candidates
+                        .value
+                        .iter()
+                        .cloned()
+                        .zip(probabilities)
+                        .collect()
+                });
+            Dispatch::<state::Probabilities>::new().set(state::Probabilities {
+                value: probabilities,
+            });
         });
         mode.value = Mode::SimulationView;
     });
@@ -49,3 +49,9 @@ pub struct Selected {
 pub struct SimulationResults {
     pub value: Option<Vec<(String, u64)>>,
 }
+
+/// Each candidate's exact chance of being chosen next
+#[derive(Debug, Default, Clone, PartialEq, Store)]
+pub struct Probabilities {
+    pub value: Option<Vec<(String, f64)>>,
+}
@@ -0,0 +1,133 @@
+//! Which participants are present for a meeting
+
+use std::{collections::HashSet, sync::LazyLock};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::NaiveDate;
+use log::warn;
+use regex::Regex;
+
+/// A name followed by a date or a range of dates
+static ABSENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(.*\S)\s+(\d{4}-\d{2}-\d{2})(?:\.\.(\d{4}-\d{2}-\d{2}))?$").unwrap()
+});
+
+/// A participant's time away, inclusive of both dates
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Absence {
+    pub name: String,
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+}
+
+impl Absence {
+    /// Parses a name followed by a date or a range of dates,
+    /// e.g., "Alice 2026-10-20..2026-10-31"
+    pub fn parse(line: &str) -> Result<Self> {
+        let groups = ABSENCE
+            .captures(line.trim())
+            .ok_or_else(|| anyhow!("cannot parse absence from line: {line}"))?;
+        let date = |s: &str| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .with_context(|| format!("parsing date {s} in line: {line}"))
+        };
+        let first = date(&groups[2])?;
+        let last = match groups.get(3) {
+            Some(last) => date(last.as_str())?,
+            None => first,
+        };
+        if last < first {
+            bail!("absence ends before it begins: {line}");
+        }
+        Ok(Self {
+            name: groups[1].to_string(),
+            first,
+            last,
+        })
+    }
+
+    pub fn covers(&self, date: NaiveDate) -> bool {
+        self.first <= date && date <= self.last
+    }
+}
+
+/// Who is and isn't available, from the various ways of saying so
+#[derive(Clone, Debug, Default)]
+pub struct Availability {
+    /// Participants absent from this meeting
+    pub absent: HashSet<String>,
+    /// If known, the only participants present
+    pub present_only: Option<HashSet<String>>,
+    pub absences: Vec<Absence>,
+}
+
+impl Availability {
+    /// Returns the participants present on `date`, keeping their order
+    pub fn present(&self, participants: &[String], date: NaiveDate) -> Vec<String> {
+        let roster = participants.iter().collect::<HashSet<_>>();
+        let mentioned = self
+            .absent
+            .iter()
+            .chain(self.present_only.iter().flatten())
+            .chain(self.absences.iter().map(|absence| &absence.name));
+        for name in mentioned {
+            if !roster.contains(name) {
+                warn!("{name} is not a participant");
+            }
+        }
+        participants
+            .iter()
+            .filter(|name| {
+                !self.absent.contains(*name)
+                    && self
+                        .present_only
+                        .as_ref()
+                        .is_none_or(|present| present.contains(*name))
+                    && !self
+                        .absences
+                        .iter()
+                        .any(|absence| &absence.name == *name && absence.covers(date))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_absence() -> Result<()> {
+        let absence = Absence::parse("Mary Ann 2026-10-20..2026-10-31")?;
+        assert_eq!(absence.name, "Mary Ann");
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        assert!(!absence.covers(date("2026-10-19")));
+        assert!(absence.covers(date("2026-10-20")));
+        assert!(absence.covers(date("2026-10-31")));
+        assert!(!absence.covers(date("2026-11-01")));
+        let absence = Absence::parse("Bob 2026-10-20")?;
+        assert_eq!(absence.first, absence.last);
+        assert!(Absence::parse("Bob 2026-10-20..2026-10-01").is_err());
+        assert!(Absence::parse("Bob next week").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_present() -> Result<()> {
+        let participants = ["Alice", "Bob", "Carl", "Doug"].map(String::from);
+        let availability = Availability {
+            absent: ["Bob".to_string()].into(),
+            present_only: None,
+            absences: vec![Absence::parse("Carl 2026-10-20..2026-10-31")?],
+        };
+        let date = NaiveDate::from_ymd_opt(2026, 10, 25).unwrap();
+        assert_eq!(availability.present(&participants, date), ["Alice", "Doug"]);
+        let date = NaiveDate::from_ymd_opt(2026, 11, 1).unwrap();
+        assert_eq!(
+            availability.present(&participants, date),
+            ["Alice", "Carl", "Doug"]
+        );
+        Ok(())
+    }
+}
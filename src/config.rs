@@ -0,0 +1,330 @@
+//! Per-team defaults for command-line options, read from a configuration file
+
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{parser::ValueSource, ArgMatches};
+use serde::Deserialize;
+
+/// The configuration file, looked for in the working directory
+/// and then in `$XDG_CONFIG_HOME`
+pub const CONFIG_FILE: &str = "nextspeaker.toml";
+
+/// Settings whose values are paths, taken relative to the configuration file
+const PATH_SETTINGS: [&str; 4] = ["participants", "history", "present-only", "availability"];
+
+/// Named teams, each with its own settings
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub teams: BTreeMap<String, Team>,
+}
+
+/// A team's settings: the participant list, and defaults for options,
+/// named as on the command line with or without underscores for dashes,
+/// e.g., `history_halflife = 5`
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Team {
+    pub settings: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    /// Reads a configuration, taking relative paths in it as relative to `dir`
+    pub fn parse(text: &str, dir: &Path) -> Result<Self> {
+        let mut config: Self = toml::from_str(text)?;
+        for team in config.teams.values_mut() {
+            for (key, value) in team.settings.iter_mut() {
+                if let (true, toml::Value::String(path)) =
+                    (PATH_SETTINGS.contains(&option_name(key).as_str()), &value)
+                {
+                    *value = toml::Value::String(dir.join(path).to_string_lossy().into_owned());
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Reads the first configuration file found, if any
+    pub fn find() -> Result<Option<Self>> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+        let candidates = [
+            Some(PathBuf::from(CONFIG_FILE)),
+            config_home.map(|dir| dir.join(CONFIG_FILE)),
+        ];
+        for path in candidates.into_iter().flatten() {
+            if !path.exists() {
+                continue;
+            }
+            let text = fs::read_to_string(&path).with_context(|| format!("reading {path:?}"))?;
+            let dir = path.parent().unwrap_or(Path::new(""));
+            return Self::parse(&text, dir)
+                .with_context(|| format!("processing {path:?}"))
+                .map(Some);
+        }
+        Ok(None)
+    }
+
+    pub fn team(&self, name: &str) -> Result<&Team> {
+        self.teams.get(name).ok_or_else(|| {
+            let names = self.teams.keys().cloned().collect::<Vec<_>>();
+            anyhow!("no team named {name}, only {}", names.join(", "))
+        })
+    }
+}
+
+impl Team {
+    /// Returns the team's settings as arguments to the subcommand that `given` was
+    /// parsed with by `cli`, leaving out settings that the subcommand has no option for
+    /// and those that `given` has from the command line. The participant list,
+    /// if the team has one, comes first unless the command line gives its own.
+    pub fn args(&self, cli: &clap::Command, given: &ArgMatches) -> Result<Vec<OsString>> {
+        let (command, given) = match given.subcommand() {
+            Some((name, given)) => (cli.find_subcommand(name), Some(given)),
+            None => (None, None),
+        };
+        let from_command_line = |id: &str| {
+            given.and_then(|given| given.value_source(id)) == Some(ValueSource::CommandLine)
+        };
+        let mut args = vec![];
+        for (key, value) in &self.settings {
+            let option = option_name(key);
+            if option == "participants" {
+                let toml::Value::String(path) = value else {
+                    bail!("participants must be a path: {value}");
+                };
+                // Positionals fill in order, so a lone one given may be another positional.
+                let positionals = command.map(|command| {
+                    let ids = command.get_positionals().map(|arg| arg.get_id().as_str());
+                    ids.filter(|id| from_command_line(id)).count()
+                        < command.get_positionals().count()
+                });
+                if positionals == Some(true) {
+                    args.insert(0, OsString::from(path));
+                }
+                continue;
+            }
+            let is_option = |arg: &clap::Arg| arg.get_long() == Some(option.as_str());
+            if !cli
+                .get_subcommands()
+                .any(|command| command.get_arguments().any(is_option))
+            {
+                bail!("unknown setting: {key}");
+            }
+            let Some(arg) =
+                command.and_then(|command| command.get_arguments().find(|arg| is_option(arg)))
+            else {
+                continue;
+            };
+            if from_command_line(arg.get_id().as_str()) {
+                continue;
+            }
+            let flag = format!("--{option}");
+            let values = match value {
+                toml::Value::Array(values) => values.clone(),
+                value => vec![value.clone()],
+            };
+            for value in values {
+                match value {
+                    toml::Value::Boolean(true) => args.push(flag.clone().into()),
+                    toml::Value::Boolean(false) => {}
+                    toml::Value::String(s) => args.extend([flag.clone().into(), s.into()]),
+                    toml::Value::Integer(_) | toml::Value::Float(_) => {
+                        args.extend([flag.clone().into(), value.to_string().into()])
+                    }
+                    value => bail!("unsupported value for {key}: {value}"),
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Returns the team given with `--team` to the subcommand in `given`, if any
+pub fn team_name(given: &ArgMatches) -> Option<String> {
+    let (_, given) = given.subcommand()?;
+    given.try_get_one::<String>("team").ok().flatten().cloned()
+}
+
+/// The command-line option a setting is for
+fn option_name(key: &str) -> String {
+    key.replace('_', "-")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{command, with_team_settings, Cli, Command};
+
+    const CONFIG: &str = r#"
+        [teams.platform]
+        participants = "platform/participants.txt"
+        history = "/shared/history.txt"
+        history_trim = true
+        history-halflife = 5
+        absent = ["Bob", "Carl"]
+        max_gap = 8
+
+        [teams.untrimmed]
+        history = "/shared/untrimmed.txt"
+        history_trim = false
+
+        [teams.typo]
+        halflife = 5
+        "#;
+
+    /// Parses `args` after filling in the team's settings
+    fn parse(args: &[&str]) -> Result<Command> {
+        let args = args.iter().map(OsString::from).collect();
+        let config = || Config::parse(CONFIG, Path::new("/etc/teams")).map(Some);
+        Ok(Cli::try_parse_args(with_team_settings(args, config)?)?.command)
+    }
+
+    #[test]
+    fn test_team() -> Result<()> {
+        let config = Config::parse(CONFIG, Path::new("/etc/teams"))?;
+        let cli = command();
+        let given = |args: &[&str]| cli.clone().ignore_errors(true).get_matches_from(args);
+        let args = config
+            .team("platform")?
+            .args(&cli, &given(&["nextspeaker", "choose", "--team=platform"]))?;
+        let expected = [
+            "/etc/teams/platform/participants.txt",
+            "--absent",
+            "Bob",
+            "--absent",
+            "Carl",
+            "--history",
+            "/shared/history.txt",
+            "--history-halflife",
+            "5",
+            "--history-trim",
+            "--max-gap",
+            "8",
+        ];
+        assert_eq!(args, expected.map(OsString::from));
+        // Validating has no use for most settings.
+        let args = config.team("platform")?.args(
+            &cli,
+            &given(&["nextspeaker", "validate", "--team=platform"]),
+        )?;
+        let expected = [
+            "/etc/teams/platform/participants.txt",
+            "--history",
+            "/shared/history.txt",
+            "--history-trim",
+        ];
+        assert_eq!(args, expected.map(OsString::from));
+        let typo = given(&["nextspeaker", "choose", "--team=typo"]);
+        assert!(config.team("typo")?.args(&cli, &typo).is_err());
+        assert!(config.team("mobile").is_err());
+        assert!(parse(&["nextspeaker", "choose", "--team=mobile"]).is_err());
+
+        let Command::Choose(args) = parse(&["nextspeaker", "choose", "--team", "platform"])? else {
+            panic!("not choosing");
+        };
+        assert_eq!(args.tunable.history_halflife, 5.0);
+        assert_eq!(args.selection.absent, ["Bob", "Carl"]);
+        assert!(args.files.history_trim);
+        assert_eq!(
+            args.files.participants,
+            PathBuf::from("/etc/teams/platform/participants.txt")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_team_overrides() -> Result<()> {
+        // Options given on the command line replace the team's.
+        let Command::Choose(args) = parse(&[
+            "nextspeaker",
+            "choose",
+            "--team=platform",
+            "other.txt",
+            "--history-halflife",
+            "7",
+            "--absent",
+            "Dee",
+            "--history-trim=false",
+        ])?
+        else {
+            panic!("not choosing");
+        };
+        assert_eq!(args.files.participants, PathBuf::from("other.txt"));
+        assert_eq!(args.tunable.history_halflife, 7.0);
+        assert_eq!(args.selection.absent, ["Dee"]);
+        assert!(!args.files.history_trim);
+        assert_eq!(args.selection.max_gap, Some(8));
+
+        // A lone positional is the name when the team has the participant list.
+        let Command::Record(args) =
+            parse(&["nextspeaker", "record", "--team", "platform", "Alice"])?
+        else {
+            panic!("not recording");
+        };
+        assert_eq!(
+            args.files.participants,
+            PathBuf::from("/etc/teams/platform/participants.txt")
+        );
+        assert_eq!(args.name, "Alice");
+        let Command::Record(args) = parse(&[
+            "nextspeaker",
+            "record",
+            "--team",
+            "platform",
+            "other.txt",
+            "Alice",
+        ])?
+        else {
+            panic!("not recording");
+        };
+        assert_eq!(args.files.participants, PathBuf::from("other.txt"));
+        assert_eq!(args.name, "Alice");
+
+        // A switch the team leaves off doesn't count as given.
+        assert!(parse(&[
+            "nextspeaker",
+            "record",
+            "--team=untrimmed",
+            "p.txt",
+            "Alice"
+        ])
+        .is_err());
+        let Command::Record(args) = parse(&[
+            "nextspeaker",
+            "record",
+            "--team=untrimmed",
+            "p.txt",
+            "Alice",
+            "--history-trim",
+        ])?
+        else {
+            panic!("not recording");
+        };
+        assert!(args.files.history_trim);
+
+        // Only the team option itself names a team.
+        let args = [
+            "nextspeaker",
+            "rename",
+            "p.txt",
+            "--history",
+            "h.txt",
+            "--",
+            "--team",
+            "x",
+        ];
+        let args = args.map(OsString::from).to_vec();
+        let unread = || -> Result<Option<Config>> { panic!("read a configuration") };
+        assert_eq!(with_team_settings(args.clone(), unread)?, args);
+        Ok(())
+    }
+}
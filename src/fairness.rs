@@ -0,0 +1,196 @@
+//! Measures of how evenly turns are spread among participants
+
+use serde::Serialize;
+
+use crate::{
+    history::{Entry, Outcome},
+    simulate::longest_wait,
+};
+
+/// Fairness measures of one or more series of turns.
+/// Measures of several series are averaged over them,
+/// except for the longest gaps, which are the longest in any series.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Fairness {
+    /// The number of turns in each series
+    pub meetings: usize,
+    /// The number of series measured
+    pub runs: usize,
+    /// Zero when everyone has as many turns, approaching one
+    /// when one participant has them all
+    pub gini: f64,
+    /// The most turns anyone had over the fewest,
+    /// or `None` if anyone had none
+    pub max_min_ratio: Option<f64>,
+    /// The standard deviation of turn counts over their mean
+    pub coefficient_of_variation: f64,
+    pub participants: Vec<ParticipantFairness>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ParticipantFairness {
+    pub name: String,
+    pub turns: f64,
+    /// The longest stretch of meetings without a turn
+    pub longest_gap: usize,
+    /// The mean number of meetings from one turn to the next,
+    /// or `None` without two turns to measure between
+    pub expected_wait: Option<f64>,
+}
+
+/// Returns who took each turn in a history:
+/// the first name in each entry for a plain choice that was kept
+pub fn turns(history: &[Entry]) -> Vec<String> {
+    history
+        .iter()
+        .filter(|entry| {
+            entry.outcome == Outcome::Spoke && entry.role.is_none() && !entry.is_grouping()
+        })
+        .filter_map(|entry| entry.names().first().map(|name| name.to_string()))
+        .collect()
+}
+
+impl Fairness {
+    /// Measures one series of turns
+    pub fn new(participants: &[String], turns: &[String]) -> Self {
+        Self::of_runs(participants, &[turns.to_vec()])
+    }
+
+    /// Measures series of turns, such as simulated runs
+    pub fn of_runs(participants: &[String], runs: &[Vec<String>]) -> Self {
+        let n_runs = runs.len().max(1) as f64;
+        let mut gini = 0.0;
+        let mut ratios = vec![];
+        let mut cv = 0.0;
+        let mut summaries = participants
+            .iter()
+            .map(|name| ParticipantFairness {
+                name: name.to_string(),
+                turns: 0.0,
+                longest_gap: 0,
+                expected_wait: None,
+            })
+            .collect::<Vec<_>>();
+        let mut waits = vec![vec![]; participants.len()];
+        for run in runs {
+            let picks = participants
+                .iter()
+                .map(|name| {
+                    run.iter()
+                        .enumerate()
+                        .filter(|(_, chosen)| *chosen == name)
+                        .map(|(m, _)| m)
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let counts = picks.iter().map(|p| p.len() as f64).collect::<Vec<_>>();
+            gini += gini_coefficient(&counts);
+            cv += coefficient_of_variation(&counts);
+            let (min, max) = counts.iter().fold((f64::INFINITY, 0.0_f64), |(lo, hi), c| {
+                (lo.min(*c), hi.max(*c))
+            });
+            if min > 0.0 && min.is_finite() {
+                ratios.push(max / min);
+            }
+            for (i, p) in picks.iter().enumerate() {
+                summaries[i].turns += p.len() as f64 / n_runs;
+                summaries[i].longest_gap = summaries[i].longest_gap.max(longest_wait(p, run.len()));
+                if p.len() >= 2 {
+                    waits[i].push((p[p.len() - 1] - p[0]) as f64 / (p.len() - 1) as f64);
+                }
+            }
+        }
+        for (summary, waits) in summaries.iter_mut().zip(waits) {
+            summary.expected_wait = mean(&waits);
+        }
+        Self {
+            meetings: runs.first().map_or(0, Vec::len),
+            runs: runs.len(),
+            gini: gini / n_runs,
+            // A ratio is only averaged if everyone had a turn in every run.
+            max_min_ratio: mean(&ratios).filter(|_| ratios.len() == runs.len()),
+            coefficient_of_variation: cv / n_runs,
+            participants: summaries,
+        }
+    }
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// The Gini coefficient of non-negative values
+pub fn gini_coefficient(values: &[f64]) -> f64 {
+    let n = values.len() as f64;
+    let total = values.iter().sum::<f64>();
+    if total == 0.0 {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let ranked = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (i + 1) as f64 * x)
+        .sum::<f64>();
+    2.0 * ranked / (n * total) - (n + 1.0) / n
+}
+
+/// The population standard deviation of values over their mean
+pub fn coefficient_of_variation(values: &[f64]) -> f64 {
+    let Some(mean) = mean(values).filter(|mean| *mean > 0.0) else {
+        return 0.0;
+    };
+    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    variance.sqrt() / mean
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::history;
+
+    #[test]
+    fn test_measures() {
+        assert_eq!(gini_coefficient(&[2.0, 2.0, 2.0]), 0.0);
+        assert!((gini_coefficient(&[0.0, 0.0, 0.0, 4.0]) - 0.75).abs() < 1e-12);
+        assert_eq!(coefficient_of_variation(&[3.0, 3.0]), 0.0);
+        assert!((coefficient_of_variation(&[1.0, 3.0]) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fairness() -> anyhow::Result<()> {
+        let participants = ["a", "b", "c"].map(String::from);
+        let history = history::parse(
+            &[
+                "a",
+                "b > c",
+                "a [declined]",
+                "c",
+                "a + b [grouping]",
+                "a [role=notes]",
+                "a",
+            ]
+            .map(String::from),
+            false,
+        )?;
+        let turns = turns(&history);
+        assert_eq!(turns, ["a", "b", "c", "a"]);
+        let fairness = Fairness::new(&participants, &turns);
+        assert_eq!(fairness.meetings, 4);
+        assert_eq!(fairness.max_min_ratio, Some(2.0));
+        let a = &fairness.participants[0];
+        assert_eq!(
+            (a.turns, a.longest_gap, a.expected_wait),
+            (2.0, 2, Some(3.0))
+        );
+        let b = &fairness.participants[1];
+        assert_eq!((b.longest_gap, b.expected_wait), (2, None));
+        let runs = [vec!["a".to_string(); 4], turns];
+        let fairness = Fairness::of_runs(&participants, &runs);
+        assert_eq!(fairness.max_min_ratio, None);
+        assert_eq!(fairness.participants[0].turns, 3.0);
+        assert_eq!(fairness.participants[1].longest_gap, 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,470 @@
+//! Splitting participants into pairs and groups, favoring those
+//! who have been together least
+
+use std::{cmp::min, collections::HashMap};
+
+use anyhow::{bail, Context, Result};
+use rand::{distributions::WeightedIndex, seq::SliceRandom, Rng};
+use rand_distr::{Beta, Distribution};
+
+use crate::{
+    history::{Entry, Outcome},
+    roster::normalize,
+    strategy::{Tally, Weight},
+    Decay, Options, RecentWindow,
+};
+
+/// Separates the members of a group, e.g., "Alice + Bob"
+pub const MEMBER_SEPARATOR: &str = "+";
+/// Separates the groups of one meeting, e.g., "Alice + Bob; Carl + Doug"
+pub const GROUP_SEPARATOR: &str = ";";
+/// The tag marking a history entry as a grouping
+pub const GROUPING_TAG: &str = "grouping";
+
+/// Formats the groups of one meeting as a history entry
+pub fn format_groups(groups: &[Vec<String>]) -> String {
+    groups
+        .iter()
+        .map(|group| group.join(&format!(" {MEMBER_SEPARATOR} ")))
+        .collect::<Vec<_>>()
+        .join(&format!("{GROUP_SEPARATOR} "))
+}
+
+/// Each pair's tally of the groupings in a history,
+/// where a pair's turn is a meeting in which they were grouped together
+#[derive(Clone, Debug)]
+pub struct PairIndex {
+    decay: Decay,
+    /// The number of groupings
+    len: usize,
+    tallies: HashMap<(String, String), PairTally>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct PairTally {
+    /// Decayed count of meetings together as of the grouping at `as_of`
+    weight: f64,
+    as_of: usize,
+}
+
+/// A pair's key, the same whichever order the names come in
+/// and however they are written
+fn pair_key(a: &str, b: &str) -> (String, String) {
+    let (a, b) = (normalize(a), normalize(b));
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+impl PairIndex {
+    /// Indexes the groupings that were kept,
+    /// ignoring entries that aren't groupings
+    pub fn new(history: &[Entry], options: &Options) -> Result<Self> {
+        let mut index = Self {
+            decay: options.decay,
+            len: 0,
+            tallies: HashMap::new(),
+        };
+        for entry in history {
+            if entry.outcome == Outcome::Spoke {
+                if let Some(groups) = entry.groups() {
+                    index.push(entry, &groups)?;
+                }
+            }
+        }
+        Ok(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn push(&mut self, entry: &Entry, groups: &[Vec<&str>]) -> Result<()> {
+        let i = self.len;
+        let weight = self.decay.entry_weight(entry)?;
+        for group in groups {
+            for (k, a) in group.iter().enumerate() {
+                for b in &group[k + 1..] {
+                    let tally = self.tallies.entry(pair_key(a, b)).or_default();
+                    tally.weight = tally.weight * self.decay.fade(i - tally.as_of) + weight;
+                    tally.as_of = i;
+                }
+            }
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Returns how much a pair has been together,
+    /// with `since_last` counting groupings since they last were
+    pub fn tally(&self, a: &str, b: &str) -> Tally {
+        match self.tallies.get(&pair_key(a, b)) {
+            None => Tally::default(),
+            Some(tally) => Tally {
+                history_weight: tally.weight * self.decay.fade(self.len - 1 - tally.as_of),
+                since_last: Some(self.len - 1 - tally.as_of),
+            },
+        }
+    }
+}
+
+/// Splits the participants into pairs, with one trio if their number is odd.
+/// Pairs are weighed like participants in `choose`, using the strategy
+/// in `options` on the history of each pair being grouped together,
+/// and pairs grouped within the recent window are avoided
+/// unless there is no one else left to pair with.
+pub fn choose_pairs(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+) -> Result<Vec<Vec<String>>> {
+    choose_pairs_with_rng(participants, history, options, &mut rand::thread_rng())
+}
+
+pub fn choose_pairs_with_rng<R: Rng + ?Sized>(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+    rng: &mut R,
+) -> Result<Vec<Vec<String>>> {
+    let index = PairIndex::new(history, options)?;
+    let recent_window = match options.recent_window {
+        RecentWindow::Auto => auto_recent_window(index.len(), participants.len()),
+        window => window.size(index.len(), participants.len()),
+    };
+    let mut remaining = participants.to_vec();
+    remaining.shuffle(rng);
+    let mut pairs = vec![];
+    while remaining.len() >= 2 {
+        let first = remaining.remove(0);
+        let tallies = remaining
+            .iter()
+            .map(|other| index.tally(&first, other))
+            .collect::<Vec<_>>();
+        let weights = sample_pair_weights(&tallies, recent_window, options, rng);
+        let partner = remaining.remove(
+            WeightedIndex::new(&weights)
+                .context("creating weighted index")?
+                .sample(rng),
+        );
+        pairs.push(vec![first, partner]);
+    }
+    if let Some(odd) = remaining.pop() {
+        // The one left over joins the pair it suits best as a trio.
+        let weights = pairs
+            .iter()
+            .map(|pair| {
+                let tallies = pair
+                    .iter()
+                    .map(|member| index.tally(&odd, member))
+                    .collect::<Vec<_>>();
+                sample_pair_weights(&tallies, recent_window, options, rng)
+                    .into_iter()
+                    .product::<f64>()
+            })
+            .collect::<Vec<_>>();
+        let weights = if weights.iter().any(|w| *w > 0.0) {
+            weights
+        } else {
+            vec![1.0; pairs.len()]
+        };
+        match WeightedIndex::new(&weights) {
+            Ok(dist) => pairs[dist.sample(rng)].push(odd),
+            // A lone participant makes a group of one.
+            Err(_) => pairs.push(vec![odd]),
+        }
+    }
+    Ok(pairs)
+}
+
+/// The number of random starting points searched from in `choose_groups`
+const RESTARTS: usize = 16;
+
+/// Returns the sizes of the groups for splitting `n` participants
+/// into groups as close to `size` as possible,
+/// with sizes differing by at most one,
+/// and with larger groups rather than someone left alone in a tie.
+/// `size` must be at least one.
+pub fn group_sizes(n: usize, size: usize) -> Vec<usize> {
+    let spread = |count: usize| {
+        (0..count)
+            .map(|i| n / count + usize::from(i < n % count))
+            .collect::<Vec<_>>()
+    };
+    let fewer = (n / size).max(1);
+    let more = n.div_ceil(size).max(1);
+    let (larger, smaller) = (spread(fewer), spread(more));
+    let excess = larger[0].saturating_sub(size);
+    let shortfall = size.saturating_sub(smaller[smaller.len() - 1]);
+    if shortfall < excess {
+        smaller
+    } else {
+        larger
+    }
+}
+
+/// Splits the participants into groups of about `size`,
+/// as described by `group_sizes`,
+/// keeping down how often people have been grouped together,
+/// counted with decay in the groupings in the history.
+/// Among groupings that are equally novel, the choice is random.
+pub fn choose_groups(
+    participants: &[String],
+    history: &[Entry],
+    size: usize,
+    options: &Options,
+) -> Result<Vec<Vec<String>>> {
+    choose_groups_with_rng(
+        participants,
+        history,
+        size,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+pub fn choose_groups_with_rng<R: Rng + ?Sized>(
+    participants: &[String],
+    history: &[Entry],
+    size: usize,
+    options: &Options,
+    rng: &mut R,
+) -> Result<Vec<Vec<String>>> {
+    if size == 0 {
+        bail!("groups need at least one member each");
+    }
+    if participants.is_empty() {
+        return Ok(vec![]);
+    }
+    let index = PairIndex::new(history, options)?;
+    let cost = |group: &[String]| {
+        group
+            .iter()
+            .enumerate()
+            .flat_map(|(k, a)| group[k + 1..].iter().map(move |b| (a, b)))
+            .map(|(a, b)| index.tally(a, b).history_weight)
+            .fold(0.0, |total, w| total + w)
+    };
+    let sizes = group_sizes(participants.len(), size);
+    let mut best: Option<(f64, Vec<Vec<String>>)> = None;
+    for _ in 0..RESTARTS {
+        let mut shuffled = participants.to_vec();
+        shuffled.shuffle(rng);
+        let mut members = shuffled.into_iter();
+        let mut groups = sizes
+            .iter()
+            .map(|size| members.by_ref().take(*size).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // Swap members between groups while that makes the grouping more novel.
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for i in 0..groups.len() {
+                for j in i + 1..groups.len() {
+                    for a in 0..groups[i].len() {
+                        for b in 0..groups[j].len() {
+                            let before = cost(&groups[i]) + cost(&groups[j]);
+                            let (left, right) = groups.split_at_mut(j);
+                            std::mem::swap(&mut left[i][a], &mut right[0][b]);
+                            if cost(&groups[i]) + cost(&groups[j]) < before - 1e-9 {
+                                improved = true;
+                            } else {
+                                let (left, right) = groups.split_at_mut(j);
+                                std::mem::swap(&mut left[i][a], &mut right[0][b]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let total = groups
+            .iter()
+            .map(|group| cost(group))
+            .fold(0.0, |t, c| t + c);
+        if best
+            .as_ref()
+            .is_none_or(|(best_total, _)| total < best_total - 1e-9)
+        {
+            best = Some((total, groups));
+        }
+    }
+    Ok(best.map(|(_, groups)| groups).unwrap_or_default())
+}
+
+/// Like `RecentWindow::Auto` for participants, the log base two
+/// of the number of groupings, but at least the last grouping,
+/// and leaving everyone at least half of the others to pair with
+fn auto_recent_window(n_groupings: usize, n_participants: usize) -> usize {
+    let recent = ((n_groupings as f64).log(2.0) as usize).max(1);
+    min(
+        min(recent, n_groupings),
+        n_participants.saturating_sub(1) / 2,
+    )
+}
+
+/// Draws weights for the pairs with the given tallies,
+/// leaving out recent pairs unless all of them are recent
+fn sample_pair_weights<R: Rng + ?Sized>(
+    tallies: &[Tally],
+    recent_window: usize,
+    options: &Options,
+    rng: &mut R,
+) -> Vec<f64> {
+    let weights = options
+        .strategy
+        .weights(tallies)
+        .into_iter()
+        .map(|weight| match weight {
+            Weight::Fixed(w) => w,
+            Weight::Beta { alpha, beta } => Beta::new(alpha, beta).unwrap().sample(rng),
+        })
+        .collect::<Vec<_>>();
+    let factors = tallies
+        .iter()
+        .map(|tally| {
+            let recent = options.strategy.excludes_recent()
+                && tally.since_last.is_some_and(|n| n < recent_window);
+            match (recent, options.recent_penalty) {
+                (false, _) => 1.0,
+                (true, Some(penalty)) => penalty,
+                (true, None) => 0.0,
+            }
+        })
+        .collect::<Vec<_>>();
+    let penalized = weights
+        .iter()
+        .zip(factors)
+        .map(|(w, factor)| w * factor)
+        .collect::<Vec<_>>();
+    if penalized.iter().any(|w| *w > 0.0) {
+        penalized
+    } else if weights.iter().any(|w| *w > 0.0) {
+        weights
+    } else {
+        vec![1.0; tallies.len()]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::history;
+
+    #[test]
+    fn test_pair_index() -> Result<()> {
+        let history = history::parse(
+            &[
+                "a + b; c + d [grouping]",
+                "x",
+                "a + c; b + d [grouping]",
+                "a + b + c [grouping] [skipped]",
+            ]
+            .map(String::from),
+            false,
+        )?;
+        let options = Options::with_halflife(1.0);
+        let index = PairIndex::new(&history, &options)?;
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.tally("b", "a").history_weight, 0.5);
+        assert_eq!(index.tally("a", "b").since_last, Some(1));
+        assert_eq!(index.tally("c", "a").history_weight, 1.0);
+        assert_eq!(index.tally("a", "d"), Tally::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_choose_pairs() -> Result<()> {
+        let participants = "abcde".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let history = history::parse(&["a + b; c + d + e [grouping]".to_string()], false)?;
+        let options = Options {
+            recent_window: RecentWindow::Entries(1),
+            ..Default::default()
+        };
+        let rng = &mut StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let pairs = choose_pairs_with_rng(&participants, &history, &options, rng)?;
+            let mut members = pairs.concat();
+            members.sort();
+            assert_eq!(members, participants);
+            assert_eq!(pairs.iter().map(Vec::len).sum::<usize>(), 5);
+            assert!(pairs.iter().all(|pair| pair.len() == 2 || pair.len() == 3));
+            for pair in &pairs {
+                let together = |a: &str, b: &str| {
+                    pair.contains(&a.to_string()) && pair.contains(&b.to_string())
+                };
+                assert!(!together("a", "b"), "{pairs:?}");
+            }
+        }
+        // By default, the last grouping is avoided.
+        let participants = &participants[..4];
+        let history = history::parse(&["a + b; c + d [grouping]".to_string()], false)?;
+        for _ in 0..100 {
+            let pairs = choose_pairs_with_rng(participants, &history, &Options::default(), rng)?;
+            assert!(!pairs.contains(&vec!["a".into(), "b".into()]), "{pairs:?}");
+            assert!(!pairs.contains(&vec!["b".into(), "a".into()]), "{pairs:?}");
+        }
+        assert_eq!(
+            format_groups(&[vec!["a".into(), "b".into()], vec!["c".into()]]),
+            "a + b; c"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_sizes() {
+        assert_eq!(group_sizes(6, 2), [2, 2, 2]);
+        assert_eq!(group_sizes(5, 2), [3, 2]);
+        assert_eq!(group_sizes(9, 5), [5, 4]);
+        assert_eq!(group_sizes(10, 4), [5, 5]);
+        assert_eq!(group_sizes(11, 4), [4, 4, 3]);
+        assert_eq!(group_sizes(2, 4), [2]);
+    }
+
+    #[test]
+    fn test_choose_groups() -> Result<()> {
+        let participants = "abcd".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let history = history::parse(
+            &["a + b; c + d [grouping]", "a + c; b + d [grouping]"].map(String::from),
+            false,
+        )?;
+        let rng = &mut StdRng::seed_from_u64(1);
+        for _ in 0..20 {
+            let mut groups =
+                choose_groups_with_rng(&participants, &history, 2, &Options::default(), rng)?;
+            for group in groups.iter_mut() {
+                group.sort();
+            }
+            groups.sort();
+            assert_eq!(groups, [["a", "d"], ["b", "c"]]);
+        }
+        let participants = "abcdefghi"
+            .chars()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        let history = history::parse(
+            &["a + b + c; d + e + f; g + h + i [grouping]".to_string()],
+            false,
+        )?;
+        let groups = choose_groups_with_rng(&participants, &history, 3, &Options::default(), rng)?;
+        for group in &groups {
+            assert_eq!(group.len(), 3);
+            // Everyone meets new faces.
+            let old = |name: &String| (name.as_bytes()[0] - b'a') / 3;
+            assert!(group
+                .iter()
+                .all(|a| group.iter().filter(|b| old(a) == old(b)).count() == 1));
+        }
+        assert!(
+            choose_groups_with_rng(&participants, &history, 0, &Options::default(), rng).is_err()
+        );
+        Ok(())
+    }
+}
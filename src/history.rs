@@ -0,0 +1,596 @@
+//! Interpretation of the entries in a history of participation
+
+use std::{collections::HashMap, fmt, str::FromStr, sync::LazyLock};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use regex::Regex;
+
+use crate::{
+    grouping::{format_groups, GROUPING_TAG, GROUP_SEPARATOR, MEMBER_SEPARATOR},
+    roster::normalize,
+    strategy::Tally,
+    Decay, MaxGap, Options, SECONDS_PER_DAY,
+};
+
+/// Separates the names in a speaking-order entry, e.g., "Alice > Bob > Carol"
+pub const ORDER_SEPARATOR: &str = ">";
+
+/// A role's name, which must fit in a tag
+static ROLE_NAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[\w-]+$").unwrap());
+/// A leading whitespace-delimited field and the rest of a line
+static LEADING_FIELD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\S+)\s+(.*)$").unwrap());
+/// The text before a trailing tag in brackets, and the tag
+static TRAILING_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.*?)\s*\[([^\]]*)\]$").unwrap());
+
+/// A part someone plays in a meeting, like "facilitator",
+/// written as a trailing tag like "Alice [role=facilitator]"
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Role(pub String);
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if !ROLE_NAME.is_match(s) {
+            bail!("role names are letters, digits, '_' and '-': {s:?}");
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// What came of a turn, written as a trailing tag like "Alice [declined]"
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Outcome {
+    /// The participant took the turn, which needs no tag
+    #[default]
+    Spoke,
+    /// The participant was chosen but said "not today"
+    Declined,
+    /// The participant was chosen but wasn't there
+    Absent,
+    /// The participant was chosen but passed over, e.g., for lack of time
+    Skipped,
+}
+
+impl FromStr for Outcome {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "spoke" => Ok(Self::Spoke),
+            "declined" => Ok(Self::Declined),
+            "absent" => Ok(Self::Absent),
+            "skipped" => Ok(Self::Skipped),
+            _ => Err(anyhow!("unknown outcome: {s}")),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Spoke => "spoke",
+            Self::Declined => "declined",
+            Self::Absent => "absent",
+            Self::Skipped => "skipped",
+        })
+    }
+}
+
+/// One line of history
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// When the entry was made, if the history records it
+    pub timestamp: Option<DateTime<Utc>>,
+    /// The name chosen, the names in a speaking order,
+    /// or the groups of a grouping
+    pub text: String,
+    /// Whether the entry splits participants into groups,
+    /// written as a "[grouping]" tag
+    pub grouping: bool,
+    /// The role the turn was for, or `None` for a plain choice
+    pub role: Option<Role>,
+    pub outcome: Outcome,
+}
+
+impl Entry {
+    /// Creates an entry without a timestamp for a turn that was taken
+    pub fn new(text: &str) -> Self {
+        Self {
+            timestamp: None,
+            text: text.to_string(),
+            grouping: false,
+            role: None,
+            outcome: Outcome::Spoke,
+        }
+    }
+
+    /// Creates an entry without a timestamp for a grouping that was kept
+    pub fn grouping(groups: &[Vec<String>]) -> Self {
+        Self {
+            grouping: true,
+            ..Self::new(&format_groups(groups))
+        }
+    }
+
+    /// Parses a line of history.
+    /// When `trim` is set, the line starts with a whitespace-delimited field
+    /// that is removed and, if it is a recognizable time, kept as the timestamp.
+    /// Trailing tags in brackets mark a grouping, as "[grouping]",
+    /// and give the role, as "[role=name]", and the outcome;
+    /// other text in brackets is part of the name.
+    pub fn parse(line: &str, trim: bool) -> Result<Self> {
+        let (timestamp, text) = if trim {
+            match LEADING_FIELD.captures(line) {
+                None => bail!("cannot trim history from line: {}", line),
+                Some(groups) => (parse_timestamp(&groups[1]), groups.get(2).unwrap().as_str()),
+            }
+        } else {
+            (None, line)
+        };
+        let mut text = text;
+        let mut grouping = false;
+        let mut role = None;
+        let mut outcome = Outcome::Spoke;
+        while let Some(groups) = TRAILING_TAG.captures(text) {
+            let tag = &groups[2];
+            if let Some(name) = tag.strip_prefix("role=") {
+                role = Some(name.parse()?);
+            } else if tag == GROUPING_TAG {
+                grouping = true;
+            } else if let Ok(tagged) = tag.parse() {
+                outcome = tagged;
+            } else {
+                // Brackets that aren't a tag are part of the name.
+                break;
+            }
+            text = groups.get(1).unwrap().as_str();
+        }
+        Ok(Self {
+            timestamp,
+            text: text.to_string(),
+            grouping,
+            role,
+            outcome,
+        })
+    }
+
+    /// Returns the names in the entry, each with its share of a turn.
+    /// A lone name gets a whole turn.
+    /// In a speaking order, the first speaker gets a whole turn,
+    /// the last gets none, and the rest get shares in proportion
+    /// to how early they spoke.
+    /// A decline scales the shares by `decline_weight`,
+    /// and absences and skips earn nothing.
+    /// Groupings aren't turns, so they give no one credit.
+    pub fn credits(&self, decline_weight: f64) -> Vec<(&str, f64)> {
+        if self.is_grouping() {
+            return vec![];
+        }
+        let scale = match self.outcome {
+            Outcome::Spoke => 1.0,
+            Outcome::Declined => decline_weight,
+            Outcome::Absent | Outcome::Skipped => 0.0,
+        };
+        if !self.text.contains(ORDER_SEPARATOR) {
+            return vec![(&self.text, scale)];
+        }
+        let names = self
+            .text
+            .split(ORDER_SEPARATOR)
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        let last = (names.len() - 1) as f64;
+        names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (name, scale * (last - i as f64) / last))
+            .collect()
+    }
+
+    /// Returns every name in the entry, whatever its form
+    pub fn names(&self) -> Vec<&str> {
+        match self.groups() {
+            Some(groups) => groups.concat(),
+            None => self.text.split(ORDER_SEPARATOR).map(str::trim).collect(),
+        }
+    }
+
+    /// Returns the entry with each name in it replaced by `rename(name)`
+    pub fn map_names(&self, mut rename: impl FnMut(&str) -> String) -> Self {
+        let text = if let Some(groups) = self.groups() {
+            let groups = groups
+                .into_iter()
+                .map(|group| group.into_iter().map(&mut rename).collect())
+                .collect::<Vec<_>>();
+            format_groups(&groups)
+        } else if self.text.contains(ORDER_SEPARATOR) {
+            let names = self
+                .text
+                .split(ORDER_SEPARATOR)
+                .map(|name| rename(name.trim()))
+                .collect::<Vec<_>>();
+            format_order(&names)
+        } else {
+            rename(self.text.trim())
+        };
+        Self {
+            text,
+            ..self.clone()
+        }
+    }
+
+    /// Whether the entry splits participants into groups,
+    /// e.g., "Alice + Bob; Carl + Doug [grouping]"
+    pub fn is_grouping(&self) -> bool {
+        self.grouping
+    }
+
+    /// Returns the members of each group, if the entry is a grouping
+    pub fn groups(&self) -> Option<Vec<Vec<&str>>> {
+        if !self.is_grouping() {
+            return None;
+        }
+        Some(
+            self.text
+                .split(GROUP_SEPARATOR)
+                .map(|group| group.split(MEMBER_SEPARATOR).map(str::trim).collect())
+                .collect(),
+        )
+    }
+
+    /// Formats the entry as a line of history that `parse` reads back,
+    /// starting with its timestamp, or "-" if it has none, when `trim` is set
+    pub fn to_line(&self, trim: bool) -> String {
+        match (trim, self.timestamp) {
+            (false, _) => self.to_string(),
+            (true, None) => format!("- {self}"),
+            (true, Some(timestamp)) => {
+                format!(
+                    "{} {self}",
+                    timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+                )
+            }
+        }
+    }
+}
+
+/// Formats the entry as a line of history, without any timestamp
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)?;
+        if self.grouping {
+            write!(f, " [{GROUPING_TAG}]")?;
+        }
+        if let Some(role) = &self.role {
+            write!(f, " [role={role}]")?;
+        }
+        match self.outcome {
+            Outcome::Spoke => Ok(()),
+            outcome => write!(f, " [{outcome}]"),
+        }
+    }
+}
+
+/// Each participant's tally of a history, computed in one pass
+/// and kept current as entries are added,
+/// so that many choices can share the work
+#[derive(Clone, Debug)]
+pub struct HistoryIndex {
+    decay: Decay,
+    decline_weight: f64,
+    role: Option<Role>,
+    cross_role_weight: f64,
+    len: usize,
+    /// The number of entries for the role whose turns were taken
+    spoken: usize,
+    /// The time of the first entry with one
+    first_timestamp: Option<DateTime<Utc>>,
+    /// Participants' tallies by their normalized names,
+    /// so that "bob " in the history counts for "Bob"
+    tallies: HashMap<String, IndexedTally>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct IndexedTally {
+    /// Decayed credit as of the entry at `as_of`
+    weight: f64,
+    as_of: usize,
+    /// Among entries for the role whose turns were taken,
+    /// the last the participant led
+    led: Option<usize>,
+    /// The time of that entry, if it has one
+    led_at: Option<DateTime<Utc>>,
+}
+
+impl HistoryIndex {
+    /// Indexes plain choices, without a role
+    pub fn new(history: &[Entry], options: &Options) -> Result<Self> {
+        Self::for_role(history, options, None)
+    }
+
+    /// Indexes the turns for one role, counting the turns for other roles,
+    /// including plain choices, by the cross-role weight in `options`.
+    /// Every entry but groupings counts toward decay by selections.
+    pub fn for_role(history: &[Entry], options: &Options, role: Option<&Role>) -> Result<Self> {
+        let mut index = Self {
+            decay: options.decay,
+            decline_weight: options.decline_weight,
+            role: role.cloned(),
+            cross_role_weight: options.cross_role_weight,
+            len: 0,
+            spoken: 0,
+            first_timestamp: None,
+            tallies: HashMap::new(),
+        };
+        for entry in history {
+            index.push(entry)?;
+        }
+        Ok(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of entries for the role whose turns were taken
+    pub fn spoken(&self) -> usize {
+        self.spoken
+    }
+
+    /// Adds an entry to the end of the history, unless it is a grouping
+    pub fn push(&mut self, entry: &Entry) -> Result<()> {
+        if entry.is_grouping() {
+            return Ok(());
+        }
+        let i = self.len;
+        let same_role = entry.role == self.role;
+        let weight = self.decay.entry_weight(entry)?
+            * if same_role {
+                1.0
+            } else {
+                self.cross_role_weight
+            };
+        let spoke = same_role && entry.outcome == Outcome::Spoke;
+        for (k, (name, credit)) in entry.credits(self.decline_weight).into_iter().enumerate() {
+            let tally = self.tallies.entry(normalize(name)).or_default();
+            // Fading is applied lazily, only when a participant's tally changes.
+            tally.weight = tally.weight * self.decay.fade(i - tally.as_of) + weight * credit;
+            tally.as_of = i;
+            if k == 0 && spoke {
+                tally.led = Some(self.spoken);
+                tally.led_at = entry.timestamp;
+            }
+        }
+        self.first_timestamp = self.first_timestamp.or(entry.timestamp);
+        self.len += 1;
+        if spoke {
+            self.spoken += 1;
+        }
+        Ok(())
+    }
+
+    pub fn tally(&self, name: &str) -> Tally {
+        match self.tallies.get(&normalize(name)) {
+            None => Tally::default(),
+            Some(tally) => Tally {
+                history_weight: tally.weight * self.decay.fade(self.len - 1 - tally.as_of),
+                since_last: tally.led.map(|i| self.spoken - 1 - i),
+            },
+        }
+    }
+
+    pub fn tallies(&self, participants: &[String]) -> Vec<Tally> {
+        participants.iter().map(|name| self.tally(name)).collect()
+    }
+
+    /// Whether a participant has gone as long as `max_gap` without a turn,
+    /// counting from the start of the history if they have never had one.
+    /// A last turn without a timestamp is never overdue by days.
+    pub fn overdue(&self, name: &str, max_gap: &MaxGap) -> bool {
+        let tally = self.tallies.get(&normalize(name));
+        let led = tally.and_then(|tally| tally.led);
+        match max_gap {
+            MaxGap::Entries(entries) => {
+                let waited = led.map_or(self.spoken, |i| self.spoken - 1 - i);
+                waited >= *entries
+            }
+            MaxGap::Days { days, now } => {
+                let since = match led {
+                    Some(_) => tally.and_then(|tally| tally.led_at),
+                    None => self.first_timestamp,
+                };
+                since.is_some_and(|since| {
+                    (*now - since).num_seconds() as f64 >= days * SECONDS_PER_DAY
+                })
+            }
+        }
+    }
+}
+
+/// Parses lines of history, trimming a leading field from each if `trim` is set
+pub fn parse(lines: &[String], trim: bool) -> Result<Vec<Entry>> {
+    lines.iter().map(|line| Entry::parse(line, trim)).collect()
+}
+
+/// Interprets an RFC 3339 time, a date, a date and time without a zone
+/// (taken as UTC), or a count of seconds since the Unix epoch
+pub fn parse_timestamp(field: &str) -> Option<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(field) {
+        return Some(t.with_timezone(&Utc));
+    }
+    if let Ok(t) = NaiveDateTime::parse_from_str(field, "%Y-%m-%dT%H:%M:%S") {
+        return Some(Utc.from_utc_datetime(&t));
+    }
+    if let Ok(d) = NaiveDate::parse_from_str(field, "%Y-%m-%d") {
+        return Some(Utc.from_utc_datetime(&d.and_hms_opt(0, 0, 0)?));
+    }
+    field
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+}
+
+/// Formats a speaking order as a history entry
+pub fn format_order(names: &[String]) -> String {
+    names.join(&format!(" {ORDER_SEPARATOR} "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = Utc.with_ymd_and_hms(2026, 10, 5, 0, 0, 0).unwrap();
+        for field in [
+            "2026-10-05",
+            "2026-10-05T00:00:00",
+            "2026-10-05T00:00:00Z",
+            "2026-10-04T20:00:00-04:00",
+            "1791158400",
+        ] {
+            assert_eq!(parse_timestamp(field), Some(expected), "{field}");
+        }
+        assert_eq!(parse_timestamp("Tuesday"), None);
+    }
+
+    #[test]
+    fn test_index() -> Result<()> {
+        let options = Options {
+            decline_weight: -0.5,
+            ..Options::with_halflife(3.0)
+        };
+        let decay = options.decay;
+        let history = parse(
+            &["a", "b > a > c", "a", "c [declined]", "b", "d > b"].map(String::from),
+            false,
+        )?;
+        let index = HistoryIndex::new(&history, &options)?;
+        for name in ["a", "b", "c", "d", "e"] {
+            // Tally directly from the definition, entry by entry.
+            let mut expected = Tally::default();
+            let spoken = history.iter().filter(|e| e.outcome == Outcome::Spoke);
+            let mut since = spoken.count();
+            for (i, entry) in history.iter().enumerate() {
+                if entry.outcome == Outcome::Spoke {
+                    since -= 1;
+                }
+                let fade = decay.fade(history.len() - 1 - i);
+                let credits = entry.credits(options.decline_weight);
+                for (k, (name_i, credit)) in credits.into_iter().enumerate() {
+                    if name_i == name {
+                        expected.history_weight += fade * credit;
+                        if k == 0 && entry.outcome == Outcome::Spoke {
+                            expected.since_last = Some(since);
+                        }
+                    }
+                }
+            }
+            let tally = index.tally(name);
+            assert!((tally.history_weight - expected.history_weight).abs() < 1e-12);
+            assert_eq!(tally.since_last, expected.since_last, "{name}");
+        }
+        let mut pushed = HistoryIndex::new(&history[..4], &options)?;
+        for entry in &history[4..] {
+            pushed.push(entry)?;
+        }
+        assert_eq!(pushed.len(), index.len());
+        pushed.push(&Entry::grouping(&[
+            vec!["a".into(), "b".into()],
+            vec!["c".into(), "d".into()],
+        ]))?;
+        assert_eq!(pushed.len(), index.len());
+        assert!((pushed.tally("c").history_weight - index.tally("c").history_weight).abs() < 1e-12);
+        Ok(())
+    }
+
+    #[test]
+    fn test_role_index() -> Result<()> {
+        let options = Options {
+            cross_role_weight: 0.25,
+            ..Default::default()
+        };
+        let history = parse(
+            &["a [role=notes]", "b", "c [role=notes]", "a"].map(String::from),
+            false,
+        )?;
+        let notes = Role("notes".to_string());
+        let index = HistoryIndex::for_role(&history, &options, Some(&notes))?;
+        let plain = HistoryIndex::new(&history, &options)?;
+        let fade = |n| options.decay.fade(n);
+        let expected = fade(3) + 0.25 * fade(0);
+        assert!((index.tally("a").history_weight - expected).abs() < 1e-12);
+        assert_eq!(index.tally("a").since_last, Some(1));
+        assert_eq!(index.tally("b").since_last, None);
+        assert_eq!(plain.tally("b").since_last, Some(1));
+        assert_eq!(plain.tally("c").since_last, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let entry = Entry::parse("2026-10-05 Mary Ann", true)?;
+        assert_eq!(entry.text, "Mary Ann");
+        assert!(entry.timestamp.is_some());
+        let entry = Entry::parse("x Bob", true)?;
+        assert_eq!(entry, Entry::new("Bob"));
+        assert_eq!(Entry::parse("x Bob", false)?, Entry::new("x Bob"));
+        assert!(Entry::parse("Bob", true).is_err());
+        let entry = Entry::parse("2026-10-05 Mary Ann [declined]", true)?;
+        assert_eq!(entry.text, "Mary Ann");
+        assert_eq!(entry.outcome, Outcome::Declined);
+        assert_eq!(entry.to_string(), "Mary Ann [declined]");
+        assert_eq!(
+            entry.to_line(true),
+            "2026-10-05T00:00:00Z Mary Ann [declined]"
+        );
+        assert_eq!(Entry::parse(&entry.to_line(true), true)?, entry);
+        assert_eq!(entry.credits(0.5), [("Mary Ann", 0.5)]);
+        let entry = Entry::parse("a > b > c [skipped]", false)?;
+        assert_eq!(entry.credits(0.5), [("a", 0.0), ("b", 0.0), ("c", 0.0)]);
+        let entry = Entry::parse("Bob [asleep] [declined]", false)?;
+        assert_eq!(entry.text, "Bob [asleep]");
+        assert_eq!(entry.outcome, Outcome::Declined);
+        assert_eq!(
+            Entry::parse("Bob (QA) [team]", false)?,
+            Entry::new("Bob (QA) [team]")
+        );
+        let entry = Entry::parse("Bob [role=notes] [absent]", false)?;
+        assert_eq!(entry.role, Some(Role("notes".to_string())));
+        assert_eq!(entry.outcome, Outcome::Absent);
+        assert_eq!(entry.to_string(), "Bob [role=notes] [absent]");
+        assert!(Entry::parse("Bob [role=]", false).is_err());
+        let entry = Entry::parse("Alice + Bob; Carl + Doug + Eve [grouping]", false)?;
+        assert_eq!(
+            entry.groups(),
+            Some(vec![vec!["Alice", "Bob"], vec!["Carl", "Doug", "Eve"]])
+        );
+        assert!(entry.credits(0.5).is_empty());
+        assert_eq!(Entry::parse(&entry.to_string(), false)?, entry);
+        // A group of one is still a grouping, and a name with "+" is just a name.
+        let alone = Entry::grouping(&[vec!["Alice".into()]]);
+        assert_eq!(alone.to_string(), "Alice [grouping]");
+        assert_eq!(
+            Entry::parse(&alone.to_string(), false)?.groups(),
+            Some(vec![vec!["Alice"]])
+        );
+        let plus = Entry::parse("Alice + Bob", false)?;
+        assert_eq!(plus.groups(), None);
+        assert_eq!(plus.credits(0.5), [("Alice + Bob", 1.0)]);
+        assert_eq!(Entry::new("Alice > Bob").groups(), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+//! Choosing speakers one after another during a meeting, as the host directs
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use rand::Rng;
+
+use crate::{
+    choose_with_rng,
+    history::{Entry, Outcome},
+    Options,
+};
+
+/// What the host can ask for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// Keep the pick, who takes the turn
+    Accept,
+    /// Choose someone else instead of the pick
+    Reroll,
+    /// Pass over the pick, who isn't there, for the rest of the meeting
+    Skip,
+    /// Choose the next speaker
+    Next,
+    Quit,
+}
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "a" | "accept" => Ok(Self::Accept),
+            "r" | "reroll" => Ok(Self::Reroll),
+            "s" | "skip" => Ok(Self::Skip),
+            "n" | "next" => Ok(Self::Next),
+            "q" | "quit" => Ok(Self::Quit),
+            _ => Err(anyhow!("unknown key: {}", s.trim())),
+        }
+    }
+}
+
+/// A meeting's picks so far, kept in a history that grows between picks
+#[derive(Clone, Debug)]
+pub struct Session {
+    /// Those present who haven't taken a turn yet
+    remaining: Vec<String>,
+    history: Vec<Entry>,
+    options: Options,
+    /// Whether a reroll counts as the pick declining
+    reroll_declines: bool,
+    pick: Option<String>,
+    /// Those rerolled since the last accepted turn
+    rerolled: Vec<String>,
+}
+
+impl Session {
+    pub fn new(
+        participants: &[String],
+        history: &[Entry],
+        options: &Options,
+        reroll_declines: bool,
+    ) -> Self {
+        Self {
+            remaining: participants.to_vec(),
+            history: history.to_vec(),
+            options: options.clone(),
+            reroll_declines,
+            pick: None,
+            rerolled: vec![],
+        }
+    }
+
+    /// The participant chosen and not yet accepted, if any
+    pub fn pick(&self) -> Option<&str> {
+        self.pick.as_deref()
+    }
+
+    /// Those present who haven't taken a turn yet
+    pub fn remaining(&self) -> &[String] {
+        &self.remaining
+    }
+
+    pub fn history(&self) -> &[Entry] {
+        &self.history
+    }
+
+    /// Acts on a key, returning the history entry it made, if any.
+    /// Accepting records a turn, rerolling may record a decline,
+    /// and skipping records an absence.
+    /// If the key can't be acted on, the session is left as it was,
+    /// without the entry.
+    pub fn press<R: Rng + ?Sized>(&mut self, key: Key, rng: &mut R) -> Result<Option<Entry>> {
+        let mut session = self.clone();
+        let entry = session.act(key, rng)?;
+        *self = session;
+        Ok(entry)
+    }
+
+    fn act<R: Rng + ?Sized>(&mut self, key: Key, rng: &mut R) -> Result<Option<Entry>> {
+        let entry = match key {
+            Key::Accept => {
+                let pick = self.take_pick()?;
+                self.remaining.retain(|name| name != &pick);
+                self.rerolled.clear();
+                Some(self.push(&pick, Outcome::Spoke))
+            }
+            Key::Reroll => {
+                let pick = self.take_pick()?;
+                self.rerolled.push(pick.clone());
+                let entry = self
+                    .reroll_declines
+                    .then(|| self.push(&pick, Outcome::Declined));
+                self.choose(rng)?;
+                entry
+            }
+            Key::Skip => {
+                let pick = self.take_pick()?;
+                self.remaining.retain(|name| name != &pick);
+                let entry = self.push(&pick, Outcome::Absent);
+                self.choose(rng)?;
+                Some(entry)
+            }
+            Key::Next => {
+                self.rerolled.clear();
+                self.choose(rng)?;
+                None
+            }
+            Key::Quit => None,
+        };
+        Ok(entry)
+    }
+
+    fn take_pick(&mut self) -> Result<String> {
+        match self.pick.take() {
+            Some(pick) => Ok(pick),
+            None => bail!("no one is picked"),
+        }
+    }
+
+    fn push(&mut self, name: &str, outcome: Outcome) -> Entry {
+        let entry = Entry {
+            timestamp: Some(Utc::now()),
+            outcome,
+            ..Entry::new(name)
+        };
+        self.history.push(entry.clone());
+        entry
+    }
+
+    /// Picks from those remaining who haven't been rerolled,
+    /// or no one if there are none
+    fn choose<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<()> {
+        let candidates = self
+            .remaining
+            .iter()
+            .filter(|name| !self.rerolled.contains(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.pick = if candidates.is_empty() {
+            None
+        } else {
+            Some(choose_with_rng(
+                &candidates,
+                &self.history,
+                &self.options,
+                rng,
+            )?)
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::strategy::{SelectionStrategy, Tally, Weight};
+
+    /// Leaves no one with any chance, so that every choice fails
+    #[derive(Debug)]
+    struct NoChance;
+
+    impl SelectionStrategy for NoChance {
+        fn weights(&self, tallies: &[Tally]) -> Vec<Weight> {
+            vec![Weight::Fixed(0.0); tallies.len()]
+        }
+    }
+
+    #[test]
+    fn test_session() -> Result<()> {
+        let participants = "abc".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let rng = &mut StdRng::seed_from_u64(1);
+        let mut session = Session::new(&participants, &[], &Options::default(), true);
+        assert!(session.press(Key::Accept, rng).is_err());
+        assert_eq!(session.press(Key::Next, rng)?, None);
+        let first = session.pick().unwrap().to_string();
+        let declined = session.press(Key::Reroll, rng)?.unwrap();
+        assert_eq!(
+            (declined.text.as_str(), declined.outcome),
+            (first.as_str(), Outcome::Declined)
+        );
+        let second = session.pick().unwrap().to_string();
+        assert_ne!(first, second);
+        let absent = session.press(Key::Skip, rng)?.unwrap();
+        assert_eq!(absent.outcome, Outcome::Absent);
+        // The first pick declined, and the second is absent, so only the third is left.
+        let third = session.pick().unwrap().to_string();
+        assert!(third != first && third != second);
+        let accepted = session.press(Key::Accept, rng)?.unwrap();
+        assert_eq!((accepted.text, accepted.outcome), (third, Outcome::Spoke));
+        assert_eq!(session.remaining(), [first.as_str()]);
+        assert_eq!(session.history().len(), 3);
+        // Rerolls are forgotten once a turn is taken.
+        session.press(Key::Next, rng)?;
+        assert_eq!(session.pick(), Some(first.as_str()));
+        assert_eq!(
+            session.press(Key::Reroll, rng)?.map(|e| e.outcome),
+            Some(Outcome::Declined)
+        );
+        assert_eq!(session.pick(), None);
+        assert_eq!("R".parse::<Key>()?, Key::Reroll);
+        assert!("x".parse::<Key>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_press() -> Result<()> {
+        let participants = "abc".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let rng = &mut StdRng::seed_from_u64(1);
+        let history = [Entry::new("a")];
+        let mut session = Session::new(&participants, &history, &Options::default(), true);
+        session.press(Key::Next, rng)?;
+        let pick = session.pick().map(str::to_string);
+        session.options.strategy = Arc::new(NoChance);
+        // Neither the decline nor the absence is kept when the next choice fails.
+        assert!(session.press(Key::Reroll, rng).is_err());
+        assert!(session.press(Key::Skip, rng).is_err());
+        assert_eq!(session.history(), history);
+        assert_eq!(session.pick(), pick.as_deref());
+        assert_eq!(session.remaining(), participants);
+        Ok(())
+    }
+}
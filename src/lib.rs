@@ -1,18 +1,204 @@
-use std::{cmp::min, path::PathBuf};
+use std::{
+    cmp::min,
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
 
-use anyhow::{anyhow, Context, Result};
-use clap::Parser;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use clap::{value_parser, ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use history::{Entry, HistoryIndex, Role};
 use log::{debug, info};
-use rand::distributions::WeightedIndex;
+use rand::{distributions::WeightedIndex, Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 use rand_distr::{Beta, Distribution};
+use strategy::{BetaDecayStrategy, SelectionStrategy, StrategyName, Weight};
+
+pub mod availability;
+pub mod config;
+pub mod fairness;
+pub mod grouping;
+pub mod history;
+pub mod interactive;
+pub mod probability;
+pub mod record;
+pub mod roster;
+pub mod simulate;
+pub mod strategy;
+pub mod tune;
+pub mod validate;
 
 pub const DEFAULT_HALFLIFE: f64 = 10.0;
+pub const DEFAULT_DECLINE_WEIGHT: f64 = 0.5;
 
+/// Chooses who speaks next, fairly over time
 #[derive(Parser, Debug)]
-pub struct Args {
-    /// The list of participants, one per line
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Choose the next speaker, or several, an order, roles, pairs, or groups
+    Choose(ChooseArgs),
+    /// Simulate choices to see how fair they are
+    Simulate(SimulateArgs),
+    /// Search for a half life and recent window that meet a fairness target
+    ///
+    /// Each setting is tried in simulated meetings,
+    /// looking for the most unpredictable one
+    /// that keeps everyone's longest wait within the target.
+    Tune(TuneArgs),
+    /// Measure how evenly turns are spread in the history
+    Stats(StatsArgs),
+    /// Check the participant list and history for likely mistakes
+    Validate(Files),
+    /// Append a turn for a participant to the history, with the time
+    Record(RecordArgs),
+    /// Remove the last entry from the history
+    Undo(UndoArgs),
+    /// Replace the name in the last history entry, keeping its timestamp and tags
+    Amend(AmendArgs),
+    /// Replace a name with another throughout the history
+    Rename(RenameArgs),
+}
+
+impl Cli {
+    /// Parses the command line, taking arguments that don't start with a command
+    /// as arguments to `choose`, so that `nextspeaker participants.txt` still works,
+    /// and filling in the settings of the team given with `--team`
+    pub fn parse_with_default_command() -> Result<Self> {
+        let args = with_default_command(std::env::args_os());
+        let args = with_team_settings(args, config::Config::find)?;
+        Ok(Self::try_parse_args(args).unwrap_or_else(|e| e.exit()))
+    }
+
+    /// Parses arguments as defined by `command`
+    pub fn try_parse_args(args: impl IntoIterator<Item = OsString>) -> Result<Self, clap::Error> {
+        let args = with_switches_resolved(args.into_iter().collect());
+        Self::from_arg_matches(&command().try_get_matches_from(args)?)
+    }
+}
+
+/// Rewrites switches given a value, as in "--history-trim=false", as given or left out,
+/// so that options requiring a switch, or conflicting with it, only count it when it's on
+fn with_switches_resolved(args: Vec<OsString>) -> Vec<OsString> {
+    let cli = Cli::command();
+    let Some(command) = args
+        .get(1)
+        .and_then(|arg| arg.to_str())
+        .and_then(|name| cli.find_subcommand(name))
+    else {
+        return args;
+    };
+    let switches = command
+        .get_arguments()
+        .filter(|arg| matches!(arg.get_action(), ArgAction::SetTrue))
+        .filter_map(|arg| arg.get_long())
+        .collect::<Vec<_>>();
+    let mut options = true;
+    args.into_iter()
+        .enumerate()
+        .filter_map(|(i, arg)| {
+            let switch = arg
+                .to_str()
+                .filter(|_| i >= 2 && options)
+                .and_then(|arg| arg.strip_prefix("--")?.split_once('='))
+                .filter(|(name, _)| switches.contains(name));
+            options &= arg != "--";
+            match switch {
+                Some((name, "true")) => Some(format!("--{name}").into()),
+                Some((_, "false")) => None,
+                _ => Some(arg),
+            }
+        })
+        .collect()
+}
+
+/// The command line as `Cli` defines it,
+/// but with each switch also taking a value, as in "--history-trim=false",
+/// so that a switch that a team turns on can be turned off.
+/// `Cli::try_parse_args` resolves these values before parsing.
+pub fn command() -> clap::Command {
+    let cli = Cli::command();
+    let switches = cli
+        .get_subcommands()
+        .flat_map(|command| {
+            command
+                .get_arguments()
+                .filter(|arg| matches!(arg.get_action(), ArgAction::SetTrue))
+                .map(|arg| (command.get_name().to_string(), arg.get_id().to_string()))
+        })
+        .collect::<Vec<_>>();
+    switches.into_iter().fold(cli, |cli, (command, switch)| {
+        cli.mut_subcommand(command, |command| {
+            command.mut_arg(switch, |arg| {
+                arg.action(ArgAction::Set)
+                    .value_parser(value_parser!(bool))
+                    .num_args(0..=1)
+                    .require_equals(true)
+                    .default_missing_value("true")
+                    .default_value("false")
+            })
+        })
+    })
+}
+
+/// Inserts the settings of the team given with `--team`, if any, after the command,
+/// leaving out those the arguments give themselves, so that they override the team's.
+/// `config` reads the configuration that defines the team.
+pub fn with_team_settings(
+    mut args: Vec<OsString>,
+    config: impl FnOnce() -> Result<Option<config::Config>>,
+) -> Result<Vec<OsString>> {
+    let cli = command();
+    // Errors, such as missing arguments the team may fill in, are for the full parse.
+    let Ok(given) = cli.clone().ignore_errors(true).try_get_matches_from(&args) else {
+        return Ok(args);
+    };
+    let Some(team) = config::team_name(&given) else {
+        return Ok(args);
+    };
+    let config = config()?
+        .ok_or_else(|| anyhow!("found no {} to read team {team} from", config::CONFIG_FILE))?;
+    let settings = config.team(&team)?.args(&cli, &given)?;
+    args.splice(2..2, settings);
+    Ok(args)
+}
+
+/// Inserts "choose" before the arguments after the program name
+/// unless they start with a command or a request for help
+pub fn with_default_command(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut args = args.into_iter().collect::<Vec<_>>();
+    let cli = Cli::command();
+    let starts_with_command = args.get(1).and_then(|arg| arg.to_str()).is_some_and(|arg| {
+        ["help", "-h", "--help"].contains(&arg)
+            || cli
+                .get_subcommands()
+                .any(|command| command.get_name() == arg)
+    });
+    if args.len() > 1 && !starts_with_command {
+        args.insert(1, "choose".into());
+    }
+    args
+}
+
+/// The participant list and history, which every command reads
+#[derive(Args, Clone, Debug)]
+pub struct Files {
+    /// The list of participants, one per line,
+    /// which can be left out for a team that has one
     pub participants: PathBuf,
 
+    /// A team whose settings in nextspeaker.toml are used
+    /// for any options not given here
+    #[arg(long)]
+    pub team: Option<String>,
+
     /// The previous history of participation
     #[arg(long)]
     pub history: Option<PathBuf>,
@@ -20,16 +206,429 @@ pub struct Args {
     /// Whether to trim an initial whitespace-delimited field from history
     #[arg(long, action)]
     pub history_trim: bool,
+}
 
+impl Files {
+    /// Returns the history's path, for commands that can't do without one
+    pub fn history_path(&self) -> Result<&Path> {
+        self.history
+            .as_deref()
+            .ok_or_else(|| anyhow!("a history is needed, given with --history"))
+    }
+}
+
+/// The half life and recent window, which `tune` searches over
+/// and the other commands that choose take as given
+#[derive(Args, Clone, Debug)]
+pub struct TunableArgs {
     /// The time for participation to matter half as much
     #[arg(long, default_value_t = DEFAULT_HALFLIFE)]
     pub history_halflife: f64,
 
-    /// The number of simulations to run
+    /// The number of days for participation to matter half as much,
+    /// using the timestamps trimmed from history
+    #[arg(long, conflicts_with = "history_halflife", requires = "history_trim")]
+    pub halflife_days: Option<f64>,
+
+    /// How many of the latest history entries make their participants recent:
+    /// "auto", "none", or a count
+    #[arg(long, default_value_t = RecentWindow::Auto)]
+    pub recent_window: RecentWindow,
+}
+
+impl Default for TunableArgs {
+    fn default() -> Self {
+        Self {
+            history_halflife: DEFAULT_HALFLIFE,
+            halflife_days: None,
+            recent_window: RecentWindow::Auto,
+        }
+    }
+}
+
+/// How participants are weighed and who is present,
+/// for every command that chooses or simulates choosing
+#[derive(Args, Clone, Debug)]
+pub struct SelectionArgs {
+    /// How participants are weighted for selection
+    #[arg(long, value_enum, default_value_t = StrategyName::BetaDecay)]
+    pub strategy: StrategyName,
+
+    /// Multiply the weights of recent participants by this factor,
+    /// more than zero and at most one, instead of excluding them
+    #[arg(long, value_parser = parse_recent_penalty)]
+    pub recent_penalty: Option<f64>,
+
+    /// The most history entries anyone may go without a turn:
+    /// participants who have gone this long are chosen over everyone else
+    #[arg(long)]
+    pub max_gap: Option<usize>,
+
+    /// The most days anyone may go without a turn,
+    /// using the timestamps trimmed from history
+    #[arg(long, conflicts_with = "max_gap", requires = "history_trim")]
+    pub max_gap_days: Option<f64>,
+
+    /// How much of a turn a decline counts as,
+    /// with a negative value counting it as a turn owed
+    #[arg(long, default_value_t = DEFAULT_DECLINE_WEIGHT, allow_negative_numbers = true)]
+    pub decline_weight: f64,
+
+    /// How much turns in other roles count toward a role's history
+    #[arg(long, default_value_t = 0.0)]
+    pub cross_role_weight: f64,
+
+    /// A participant absent from this meeting, who may be given repeatedly
+    #[arg(long)]
+    pub absent: Vec<String>,
+
+    /// A file listing the only participants present, one per line
+    #[arg(long)]
+    pub present_only: Option<PathBuf>,
+
+    /// A file of absences, one per line, each a name followed by a date
+    /// or a range of dates, e.g., "Alice 2026-10-20..2026-10-31"
+    #[arg(long)]
+    pub availability: Option<PathBuf>,
+
+    /// The date of the meeting, for checking availability, defaulting to today
+    #[arg(long)]
+    pub date: Option<NaiveDate>,
+
+    /// Seed for the random number generator, making choices reproducible;
+    /// the generator is ChaCha12, so a seed replays the same draws across releases
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+/// Parses a recent penalty, which must leave recent participants some chance
+/// without favoring them
+fn parse_recent_penalty(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(penalty) if penalty > 0.0 && penalty <= 1.0 => Ok(penalty),
+        Ok(_) => Err("the penalty must be more than 0 and at most 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[derive(Args, Debug)]
+#[command(group(
+    clap::ArgGroup::new("mode").args(["count", "order", "roles", "pairs", "group_size"])
+))]
+pub struct ChooseArgs {
+    #[command(flatten)]
+    pub files: Files,
+
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+
+    #[command(flatten)]
+    pub tunable: TunableArgs,
+
+    /// Record in the history that this participant declined, and choose again
+    #[arg(long, requires = "history")]
+    pub decline: Option<String>,
+
+    /// The number of distinct participants to choose
     #[arg(long)]
-    pub n_simulations: Option<usize>,
+    pub count: Option<usize>,
+
+    /// Whether to order all participants for speaking
+    #[arg(long, action)]
+    pub order: bool,
+
+    /// Roles to assign to distinct participants, separated by commas,
+    /// e.g., "facilitator,notes"
+    #[arg(long, value_delimiter = ',')]
+    pub roles: Vec<Role>,
+
+    /// Whether to split all participants into pairs, with a trio if needed
+    #[arg(long, action)]
+    pub pairs: bool,
+
+    /// Split all participants into breakout groups of about this size
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub group_size: Option<usize>,
+
+    /// Whether to show the weights behind the choice, which is of one speaker
+    #[arg(long, action, conflicts_with = "mode")]
+    pub explain: bool,
+
+    /// Append the choice to the history, with the time, while no one else is
+    #[arg(long, action, requires_all = ["history", "history_trim"])]
+    pub record: bool,
+
+    /// Choose speakers one after another, as keys entered direct,
+    /// keeping the history up to date between picks
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["mode", "explain"]
+    )]
+    pub interactive: bool,
+
+    /// Whether rerolling a pick in interactive mode counts as the pick declining
+    #[arg(long, action, requires = "interactive")]
+    pub reroll_declines: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SimulateArgs {
+    #[command(flatten)]
+    pub files: Files,
+
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+
+    #[command(flatten)]
+    pub tunable: TunableArgs,
+
+    /// The number of times to draw the next choice from the same history,
+    /// counting how often each participant is chosen
+    #[arg(long, conflicts_with = "meetings")]
+    pub draws: Option<usize>,
+
+    /// The number of future meetings to simulate,
+    /// each choice joining the history before the next
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub meetings: Option<usize>,
+
+    /// The number of independent runs of simulated meetings
+    #[arg(
+        long,
+        default_value_t = 100,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub runs: usize,
+
+    /// Whether to measure how evenly turns are spread in the simulated meetings
+    #[arg(long, action, requires = "meetings")]
+    pub fairness: bool,
+
+    /// Whether to write the fairness measures as JSON
+    #[arg(long, action, requires = "fairness")]
+    pub json: bool,
 }
 
+#[derive(Args, Debug)]
+pub struct TuneArgs {
+    #[command(flatten)]
+    pub files: Files,
+
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+
+    /// The number of future meetings in each simulated run,
+    /// defaulting to four per participant
+    #[arg(long)]
+    pub meetings: Option<usize>,
+
+    /// The number of independent runs for each setting tried
+    #[arg(long, default_value_t = 100)]
+    pub runs: usize,
+
+    /// The target longest wait, as a multiple of the number of participants
+    #[arg(long, default_value_t = 2.0)]
+    pub max_wait_factor: f64,
+}
+
+#[derive(Args, Debug)]
+pub struct StatsArgs {
+    #[command(flatten)]
+    pub files: Files,
+
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+
+    #[command(flatten)]
+    pub tunable: TunableArgs,
+
+    /// Whether to show each present participant's chance of being chosen next
+    /// instead of measuring the history
+    #[arg(long, action)]
+    pub probabilities: bool,
+
+    /// Whether to write the fairness measures as JSON
+    #[arg(long, action, conflicts_with = "probabilities")]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct RecordArgs {
+    #[command(flatten)]
+    pub files: Files,
+
+    /// The participant who took a turn
+    #[arg(requires_all = ["history", "history_trim"])]
+    pub name: String,
+}
+
+#[derive(Args, Debug)]
+pub struct UndoArgs {
+    #[command(flatten)]
+    pub files: Files,
+
+    /// Only undo an entry recorded with a timestamp within this many minutes
+    #[arg(long, requires = "history_trim")]
+    pub within: Option<u64>,
+}
+
+#[derive(Args, Debug)]
+pub struct AmendArgs {
+    #[command(flatten)]
+    pub files: Files,
+
+    /// The participant who took the turn instead
+    pub name: String,
+}
+
+#[derive(Args, Debug)]
+pub struct RenameArgs {
+    #[command(flatten)]
+    pub files: Files,
+
+    /// The name to replace
+    pub old: String,
+
+    /// The name to replace it with
+    pub new: String,
+}
+
+impl SelectionArgs {
+    /// Creates the random number generator for this run,
+    /// seeded from `--seed` if given and from entropy otherwise.
+    /// Unlike `StdRng`, its algorithm is fixed, so seeded draws can be replayed later.
+    pub fn rng(&self) -> ChaCha12Rng {
+        match self.seed {
+            Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+            None => ChaCha12Rng::from_entropy(),
+        }
+    }
+
+    pub fn meeting_date(&self) -> NaiveDate {
+        self.date.unwrap_or_else(|| Local::now().date_naive())
+    }
+
+    pub fn options(&self, tunable: &TunableArgs) -> Options {
+        let decay = match tunable.halflife_days {
+            Some(halflife) => Decay::Days {
+                halflife,
+                now: Utc::now(),
+            },
+            None => Decay::Selections(tunable.history_halflife),
+        };
+        let max_gap = match (self.max_gap, self.max_gap_days) {
+            (Some(entries), _) => Some(MaxGap::Entries(entries)),
+            (None, Some(days)) => Some(MaxGap::Days {
+                days,
+                now: Utc::now(),
+            }),
+            (None, None) => None,
+        };
+        Options {
+            decay,
+            strategy: self.strategy.strategy(),
+            recent_window: tunable.recent_window,
+            recent_penalty: self.recent_penalty,
+            max_gap,
+            decline_weight: self.decline_weight,
+            cross_role_weight: self.cross_role_weight,
+        }
+    }
+}
+
+/// How the influence of past participation fades
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decay {
+    /// Halving with every given number of later history entries
+    Selections(f64),
+    /// Halving with every given number of days between an entry's timestamp and `now`
+    Days { halflife: f64, now: DateTime<Utc> },
+}
+
+impl Decay {
+    /// The time to give new history entries, if timestamps matter
+    pub fn now(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Selections(_) => None,
+            Self::Days { now, .. } => Some(*now),
+        }
+    }
+
+    /// How much an entry's weight fades over the given number of later entries
+    pub fn fade(&self, entries: usize) -> f64 {
+        match self {
+            Self::Selections(halflife) => exponentially_weighted_decay(*halflife, entries as f64),
+            Self::Days { .. } => 1.0,
+        }
+    }
+
+    /// How much an entry counts before fading with later entries
+    pub fn entry_weight(&self, entry: &Entry) -> Result<f64> {
+        match self {
+            Self::Selections(_) => Ok(1.0),
+            Self::Days { halflife, now } => {
+                let timestamp = entry
+                    .timestamp
+                    .ok_or_else(|| anyhow!("no timestamp for history entry: {}", entry.text))?;
+                let seconds = (*now - timestamp).num_seconds().max(0) as f64;
+                Ok(exponentially_weighted_decay(
+                    *halflife,
+                    seconds / SECONDS_PER_DAY,
+                ))
+            }
+        }
+    }
+}
+
+/// How long anyone may go without a turn before being chosen over everyone else
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MaxGap {
+    /// A count of history entries whose turns were taken
+    Entries(usize),
+    /// A number of days between the participant's last turn and `now`
+    Days { days: f64, now: DateTime<Utc> },
+}
+
+/// Settings that shape a selection
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub decay: Decay,
+    pub strategy: Arc<dyn SelectionStrategy>,
+    pub recent_window: RecentWindow,
+    /// A factor for the weights of recent participants,
+    /// who are otherwise excluded
+    pub recent_penalty: Option<f64>,
+    /// How long anyone may go without a turn, if there is a limit
+    pub max_gap: Option<MaxGap>,
+    /// How much of a turn a decline counts as, negative for a turn owed
+    pub decline_weight: f64,
+    /// How much turns in other roles count toward a role's history
+    pub cross_role_weight: f64,
+}
+
+impl Options {
+    pub fn with_halflife(history_halflife: f64) -> Self {
+        Self {
+            decay: Decay::Selections(history_halflife),
+            strategy: Arc::new(BetaDecayStrategy),
+            recent_window: RecentWindow::Auto,
+            recent_penalty: None,
+            max_gap: None,
+            decline_weight: DEFAULT_DECLINE_WEIGHT,
+            cross_role_weight: 0.0,
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::with_halflife(DEFAULT_HALFLIFE)
+    }
+}
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
 fn exponentially_weighted_decay(half_life: f64, time: f64) -> f64 {
     0.5_f64.powf(time / half_life)
 }
@@ -39,74 +638,263 @@ fn n_recent_for_history_and_participants(n_history: usize, n_participants: usize
     min(recent, n_participants / 2)
 }
 
-pub fn choose(
+/// How many of the latest history entries make their leaders recent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecentWindow {
+    /// The log base two of the number of turns taken, but at most half the participants
+    Auto,
+    /// Nobody is recent
+    None,
+    Entries(usize),
+}
+
+impl RecentWindow {
+    pub fn size(&self, n_history: usize, n_participants: usize) -> usize {
+        match self {
+            Self::Auto => n_recent_for_history_and_participants(n_history, n_participants),
+            Self::None => 0,
+            Self::Entries(n) => min(*n, n_history),
+        }
+    }
+}
+
+impl FromStr for RecentWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "none" => Ok(Self::None),
+            _ => Ok(Self::Entries(s.parse().with_context(|| {
+                format!("recent window is not \"auto\", \"none\", or a count: {s}")
+            })?)),
+        }
+    }
+}
+
+impl fmt::Display for RecentWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::None => write!(f, "none"),
+            Self::Entries(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+/// One participant's part in a selection
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub name: String,
+    /// Decayed count of the participant's previous selections
+    pub history_weight: f64,
+    /// The Beta distribution parameters the weight was sampled from,
+    /// or `None` when there's no history and every weight is one
+    pub beta: Option<(f64, f64)>,
+    /// The weight sampled for this selection
+    pub weight: f64,
+    /// Whether the participant led one of the entries in the recent window
+    pub recent: bool,
+    /// Whether the participant was excluded for being chosen recently
+    pub excluded: bool,
+    /// Whether the participant had gone too long without a turn,
+    /// and so was chosen over everyone who hadn't
+    pub overdue: bool,
+    /// The chance of selection given the sampled weights
+    pub probability: f64,
+}
+
+/// The outcome of a selection along with the reasons for it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selection {
+    pub chosen: String,
+    pub candidates: Vec<Candidate>,
+    /// The number of latest history entries whose leaders count as recent
+    pub recent_window: usize,
+}
+
+pub fn choose(participants: &[String], history: &[Entry], options: &Options) -> Result<String> {
+    choose_with_rng(participants, history, options, &mut rand::thread_rng())
+}
+
+/// Like `choose`, but drawing all randomness from `rng`,
+/// so that a seeded generator gives reproducible choices.
+pub fn choose_with_rng<R: Rng + ?Sized>(
     participants: &[String],
-    history: &[String],
-    history_halflife: f64,
+    history: &[Entry],
+    options: &Options,
+    rng: &mut R,
 ) -> Result<String> {
-    debug!("history:{history:?}");
-    let rng = &mut rand::thread_rng();
-    let weights = if history.is_empty() {
-        vec![1.0; participants.len()]
-    } else {
-        let decay = history
-            .iter()
-            .rev()
-            .enumerate()
-            .map(|(i, _)| {
-                let t = i as f64;
-                exponentially_weighted_decay(history_halflife, t)
-            })
-            .rev()
-            .collect::<Vec<_>>();
-        let recent = n_recent_for_history_and_participants(history.len(), participants.len());
-        let history_weights: Vec<_> = participants
-            .iter()
-            .map(|name| {
-                history
-                    .iter()
-                    .enumerate()
-                    .filter_map(
-                        |(i, name_i)| {
-                            if name_i == name {
-                                Some(decay[i])
-                            } else {
-                                None
-                            }
-                        },
-                    )
-                    .sum::<f64>()
-            })
-            .collect();
-        let mut recent_participants = vec![];
-        let mut weights: Vec<_> = participants
-            .iter()
-            .zip(history_weights.iter())
-            .enumerate()
-            .map(|(i, (name, &weight_past))| {
-                if history
-                    .iter()
-                    .skip(history.len() - recent)
-                    .any(|p| p == name)
-                {
-                    recent_participants.push(i);
+    let index = HistoryIndex::new(history, options)?;
+    Ok(choose_from_index(participants, &index, options, rng)?.chosen)
+}
+
+/// Chooses `count` distinct participants in speaking order.
+/// Each choice joins the history before the next is drawn,
+/// so decay and recent exclusion account for earlier choices.
+pub fn choose_many(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+    count: usize,
+) -> Result<Vec<String>> {
+    choose_many_with_rng(
+        participants,
+        history,
+        options,
+        count,
+        &mut rand::thread_rng(),
+    )
+}
+
+pub fn choose_many_with_rng<R: Rng + ?Sized>(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+    count: usize,
+    rng: &mut R,
+) -> Result<Vec<String>> {
+    if count > participants.len() {
+        bail!(
+            "cannot choose {count} of {} participants",
+            participants.len()
+        );
+    }
+    let mut remaining = participants.to_vec();
+    let mut index = HistoryIndex::new(history, options)?;
+    let mut chosen = vec![];
+    for _ in 0..count {
+        let selection = choose_from_index(&remaining, &index, options, rng)?.chosen;
+        remaining.retain(|name| name != &selection);
+        index.push(&Entry {
+            timestamp: options.decay.now(),
+            ..Entry::new(&selection)
+        })?;
+        chosen.push(selection);
+    }
+    Ok(chosen)
+}
+
+/// Chooses a distinct participant for each role, in the order given.
+/// Each role is weighed by its own history of turns.
+pub fn choose_roles(
+    participants: &[String],
+    history: &[Entry],
+    roles: &[Role],
+    options: &Options,
+) -> Result<Vec<String>> {
+    choose_roles_with_rng(
+        participants,
+        history,
+        roles,
+        options,
+        &mut rand::thread_rng(),
+    )
+}
+
+pub fn choose_roles_with_rng<R: Rng + ?Sized>(
+    participants: &[String],
+    history: &[Entry],
+    roles: &[Role],
+    options: &Options,
+    rng: &mut R,
+) -> Result<Vec<String>> {
+    if roles.len() > participants.len() {
+        bail!(
+            "cannot fill {} roles from {} participants",
+            roles.len(),
+            participants.len()
+        );
+    }
+    let mut remaining = participants.to_vec();
+    let mut chosen = vec![];
+    for role in roles {
+        let index = HistoryIndex::for_role(history, options, Some(role))?;
+        let selection = choose_from_index(&remaining, &index, options, rng)
+            .with_context(|| format!("choosing {role}"))?
+            .chosen;
+        remaining.retain(|name| name != &selection);
+        chosen.push(selection);
+    }
+    Ok(chosen)
+}
+
+/// Orders all participants for speaking, with those chosen less
+/// in the past tending to speak earlier.
+/// `history::format_order` makes a history entry of the result.
+pub fn order(participants: &[String], history: &[Entry], options: &Options) -> Result<Vec<String>> {
+    order_with_rng(participants, history, options, &mut rand::thread_rng())
+}
+
+pub fn order_with_rng<R: Rng + ?Sized>(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+    rng: &mut R,
+) -> Result<Vec<String>> {
+    choose_many_with_rng(participants, history, options, participants.len(), rng)
+}
+
+/// Like `choose`, but returning the weights behind the choice.
+pub fn choose_explained(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+) -> Result<Selection> {
+    choose_explained_with_rng(participants, history, options, &mut rand::thread_rng())
+}
+
+pub fn choose_explained_with_rng<R: Rng + ?Sized>(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+    rng: &mut R,
+) -> Result<Selection> {
+    let index = HistoryIndex::new(history, options)?;
+    choose_from_index(participants, &index, options, rng)
+}
+
+/// Like `choose_explained_with_rng`, but using an index of the history,
+/// which can be reused for many choices.
+/// The index must have been made with the same `options`.
+pub fn choose_from_index<R: Rng + ?Sized>(
+    participants: &[String],
+    index: &HistoryIndex,
+    options: &Options,
+    rng: &mut R,
+) -> Result<Selection> {
+    let (prospects, recent_window) = prospects(participants, index, options)?;
+    let mut candidates = prospects
+        .iter()
+        .map(|prospect| {
+            let (beta, weight) = match prospect.weight {
+                Weight::Fixed(weight) => (None, weight),
+                Weight::Beta { alpha, beta } => {
+                    let dist = Beta::new(alpha, beta).unwrap();
+                    (Some((alpha, beta)), dist.sample(rng))
                 }
-                // Beta distribution will lean toward zero weight
-                // the more a participant has been previously selected.
-                debug!("participant:{name} history weight:{weight_past}");
-                let dist = Beta::new(1_f64, 1_f64 + weight_past).unwrap();
-                dist.sample(rng)
-            })
-            .collect();
-        info!("recent participants:{recent_participants:?}");
-        // Exclude recently selected participants unless everyone's recent
-        if recent_participants.len() < participants.len() {
-            for i in recent_participants {
-                weights[i] = 0.0;
+            };
+            Candidate {
+                name: prospect.name.clone(),
+                history_weight: prospect.history_weight,
+                beta,
+                weight,
+                recent: prospect.recent,
+                excluded: prospect.excluded,
+                overdue: prospect.overdue,
+                probability: 0.0,
             }
-        }
-        weights
-    };
+        })
+        .collect::<Vec<_>>();
+    let weights = candidates
+        .iter()
+        .zip(prospects.iter())
+        .map(|(c, prospect)| c.weight * prospect.factor)
+        .collect::<Vec<_>>();
+    let total_weight = weights.iter().sum::<f64>();
+    for (c, w) in candidates.iter_mut().zip(weights.iter()) {
+        c.probability = w / total_weight;
+    }
     let weight_info = weights
         .iter()
         .zip(participants.iter())
@@ -114,10 +902,145 @@ pub fn choose(
         .collect::<Vec<_>>();
     info!("participant selection weights:{weight_info:?}");
     let dist = WeightedIndex::new(&weights).context("creating weighted index")?;
-    Ok(participants
+    let chosen = participants
         .get(dist.sample(rng))
         .ok_or_else(|| anyhow!("weighted index sample is not in bounds"))?
-        .to_string())
+        .to_string();
+    Ok(Selection {
+        chosen,
+        candidates,
+        recent_window,
+    })
+}
+
+/// Computes each participant's chance of being chosen by `choose`,
+/// integrating over the random weights instead of sampling them
+pub fn probabilities(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+) -> Result<Vec<f64>> {
+    let index = HistoryIndex::new(history, options)?;
+    probabilities_from_index(participants, &index, options)
+}
+
+/// Like `probabilities`, but using an index of the history
+pub fn probabilities_from_index(
+    participants: &[String],
+    index: &HistoryIndex,
+    options: &Options,
+) -> Result<Vec<f64>> {
+    let (prospects, _) = prospects(participants, index, options)?;
+    let weights = prospects
+        .iter()
+        .map(|prospect| (prospect.weight, prospect.factor))
+        .collect::<Vec<_>>();
+    Ok(probability::selection_probabilities(&weights))
+}
+
+/// A participant's part in a selection before any weight is sampled
+struct Prospect {
+    name: String,
+    history_weight: f64,
+    weight: Weight,
+    recent: bool,
+    excluded: bool,
+    overdue: bool,
+    /// Scales the sampled weight, for exclusion, a recent penalty, or others being overdue
+    factor: f64,
+}
+
+/// Works out everything about a selection that doesn't depend on chance,
+/// returning it with the size of the recent window
+fn prospects(
+    participants: &[String],
+    index: &HistoryIndex,
+    options: &Options,
+) -> Result<(Vec<Prospect>, usize)> {
+    // Only turns taken make anyone recent, so only they widen the window.
+    let recent_window = options
+        .recent_window
+        .size(index.spoken(), participants.len());
+    info!("recent window:{recent_window}");
+    if index.is_empty() {
+        let prospects = participants
+            .iter()
+            .map(|name| Prospect {
+                name: name.to_string(),
+                history_weight: 0.0,
+                weight: Weight::Fixed(1.0),
+                recent: false,
+                excluded: false,
+                overdue: false,
+                factor: 1.0,
+            })
+            .collect();
+        return Ok((prospects, recent_window));
+    }
+    let tallies = index.tallies(participants);
+    let weights = options.strategy.weights(&tallies);
+    let mut prospects = participants
+        .iter()
+        .zip(tallies.iter().zip(weights))
+        .map(|(name, (tally, weight))| {
+            debug!("participant:{name} history weight:{}", tally.history_weight);
+            let recent = options.strategy.excludes_recent()
+                && tally.since_last.is_some_and(|n| n < recent_window);
+            Prospect {
+                name: name.to_string(),
+                history_weight: tally.history_weight,
+                weight,
+                recent,
+                excluded: recent && options.recent_penalty.is_none(),
+                overdue: options
+                    .max_gap
+                    .is_some_and(|max_gap| index.overdue(name, &max_gap)),
+                factor: match (recent, options.recent_penalty) {
+                    (false, _) => 1.0,
+                    (true, Some(penalty)) => penalty,
+                    (true, None) => 0.0,
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+    let recent_participants = prospects
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| if p.recent { Some(i) } else { None })
+        .collect::<Vec<_>>();
+    info!("recent participants:{recent_participants:?}");
+    // Exclude or penalize recently selected participants unless everyone's recent
+    if recent_participants.len() == participants.len() {
+        for p in prospects.iter_mut() {
+            p.excluded = false;
+            p.factor = 1.0;
+        }
+    }
+    // Only overdue participants can be chosen, whether recent or not
+    if prospects.iter().any(|p| p.overdue) {
+        for p in prospects.iter_mut() {
+            p.factor = if p.overdue { 1.0 } else { 0.0 };
+        }
+        // A strategy may give every overdue participant no weight,
+        // e.g., round robin when someone else has waited more entries,
+        // so they are then chosen among evenly
+        if prospects
+            .iter()
+            .filter(|p| p.overdue)
+            .all(|p| matches!(p.weight, Weight::Fixed(w) if w <= 0.0))
+        {
+            for p in prospects.iter_mut().filter(|p| p.overdue) {
+                p.weight = Weight::Fixed(1.0);
+            }
+        }
+        let overdue = prospects
+            .iter()
+            .filter(|p| p.overdue)
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        info!("overdue participants:{overdue:?}");
+    }
+    Ok((prospects, recent_window))
 }
 
 #[cfg(test)]
@@ -172,7 +1095,7 @@ mod test {
                 .collect();
             let mut history: Vec<_> = history_slices
                 .iter()
-                .map(|hist_slice| {
+                .flat_map(|hist_slice| {
                     let weights = hist_slice.weights(n_participants);
                     (0..hist_slice.duration)
                         .map(|_| {
@@ -186,7 +1109,6 @@ mod test {
                         })
                         .collect::<Vec<_>>()
                 })
-                .flatten()
                 .collect::<Vec<_>>();
             let n_recent = n_recent_for_history_and_participants(history.len(), n_participants);
             loop {
@@ -222,6 +1144,14 @@ mod test {
 
     const N_REPS: usize = 1000;
 
+    fn entries(names: &[String]) -> Vec<Entry> {
+        names.iter().map(|name| Entry::new(name)).collect()
+    }
+
+    fn entries_names(history: &[Entry]) -> Vec<String> {
+        history.iter().map(|entry| entry.text.clone()).collect()
+    }
+
     fn init() -> Result<()> {
         Ok(simple_logger::init_with_env()?)
     }
@@ -253,17 +1183,19 @@ mod test {
         );
         debug!("scenario:\n{s:?}");
         let halflife_vals = [1000.0, 1.0];
+        let history = entries(&s.history);
+        let options = halflife_vals.map(Options::with_halflife);
         let mut selected = halflife_vals
             .iter()
             .map(|_| HashMap::new())
             .collect::<Vec<_>>();
         for _ in 0..N_REPS {
             info!("halflife:{}", halflife_vals[0]);
-            let selection = choose(&s.participants, &s.history, halflife_vals[0])?;
+            let selection = choose(&s.participants, &history, &options[0])?;
             *selected[0].entry(selection).or_insert(0_usize) += 1;
 
             info!("halflife:{}", halflife_vals[1]);
-            let selection = choose(&s.participants, &s.history, halflife_vals[1])?;
+            let selection = choose(&s.participants, &history, &options[1])?;
             *selected[1].entry(selection).or_insert(0_usize) += 1;
         }
         debug!("selected:{selected:?}");
@@ -280,16 +1212,445 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_seed() -> Result<()> {
+        let participants = "abcdefghij"
+            .chars()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        let history = entries(&["a".to_string(), "b".to_string(), "a".to_string()]);
+        let draws = |seed| {
+            let mut args = SelectionArgs::dummy();
+            args.seed = Some(seed);
+            let rng = &mut args.rng();
+            (0..N_REPS)
+                .map(|_| {
+                    choose_with_rng(
+                        &participants,
+                        &history,
+                        &args.options(&TunableArgs::default()),
+                        rng,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        assert_eq!(draws(42)?, draws(42)?);
+        assert_ne!(draws(42)?, draws(43)?);
+        // The generator is pinned, so a seed gives these draws in every release.
+        assert_eq!(draws(42)?[..8], ["h", "f", "e", "i", "c", "f", "e", "i"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_explained() -> Result<()> {
+        let participants = "abcd".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let history = entries(&["a".to_string(), "a".to_string(), "b".to_string()]);
+        let selection = choose_explained(&participants, &history, &Options::default())?;
+        let by_name = |name: &str| {
+            selection
+                .candidates
+                .iter()
+                .find(|c| c.name == name)
+                .unwrap()
+                .clone()
+        };
+        assert!(by_name("a").history_weight > by_name("b").history_weight);
+        assert_eq!(by_name("c").history_weight, 0.0);
+        assert_eq!(by_name("c").beta, Some((1.0, 1.0)));
+        assert!(by_name("b").excluded);
+        assert_eq!(by_name("b").probability, 0.0);
+        assert_ne!(selection.chosen, "b");
+        let total = selection
+            .candidates
+            .iter()
+            .map(|c| c.probability)
+            .sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_choose_many() -> Result<()> {
+        let participants = "abcdef".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let history = entries(&participants);
+        let options = Options::default();
+        for _ in 0..N_REPS {
+            let mut chosen = choose_many(&participants, &history, &options, 3)?;
+            assert_eq!(chosen.len(), 3);
+            chosen.sort();
+            chosen.dedup();
+            assert_eq!(chosen.len(), 3);
+        }
+        let everyone = choose_many(&participants, &history, &options, participants.len())?;
+        assert_eq!(everyone.len(), participants.len());
+        assert!(choose_many(&participants, &history, &options, 7).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_order() -> Result<()> {
+        let participants = "abcd".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let history = vec![Entry::new(&history::format_order(&participants)); 30];
+        let mut firsts = HashMap::new();
+        for _ in 0..N_REPS {
+            let mut speakers = order(&participants, &history, &Options::default())?;
+            *firsts.entry(speakers[0].clone()).or_insert(0_usize) += 1;
+            speakers.sort();
+            assert_eq!(speakers, participants);
+        }
+        let first_count = |name: &str| *firsts.get(name).unwrap_or(&0);
+        assert_eq!(first_count("a"), 0);
+        assert!(first_count("b") < first_count("d"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_halflife_days() -> Result<()> {
+        let now = Utc::now();
+        let days_ago = |days| now - chrono::Duration::days(days);
+        let history = [("a", 1), ("b", 30), ("c", 300)]
+            .into_iter()
+            .map(|(name, days)| Entry {
+                timestamp: Some(days_ago(days)),
+                ..Entry::new(name)
+            })
+            .collect::<Vec<_>>();
+        let decay = Decay::Days {
+            halflife: 30.0,
+            now,
+        };
+        let options = Options {
+            decay,
+            ..Default::default()
+        };
+        let index = HistoryIndex::new(&history, &options)?;
+        let weights = index
+            .tallies(&entries_names(&history))
+            .iter()
+            .map(|tally| tally.history_weight)
+            .collect::<Vec<_>>();
+        assert!((weights[1] - 0.5).abs() < 1e-9);
+        assert!(weights[0] > weights[1] && weights[1] > weights[2]);
+        assert!(HistoryIndex::new(&[Entry::new("a")], &options).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_strategies() -> Result<()> {
+        let participants = "abcd".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let history = entries(&["c", "a", "b", "a", "b"].map(String::from));
+        let mut options = Options {
+            strategy: StrategyName::RoundRobin.strategy(),
+            ..Default::default()
+        };
+        for _ in 0..N_REPS {
+            assert_eq!(choose(&participants, &history, &options)?, "d");
+        }
+        options.strategy = StrategyName::InverseCount.strategy();
+        let selection = choose_explained(&participants, &history, &options)?;
+        let weights = selection
+            .candidates
+            .iter()
+            .map(|c| c.weight)
+            .collect::<Vec<_>>();
+        assert!(weights[0] < weights[2] && weights[2] < weights[3]);
+        assert!(selection.candidates.iter().all(|c| c.beta.is_none()));
+        options.strategy = StrategyName::Uniform.strategy();
+        let selection = choose_explained(&participants, &history, &options)?;
+        assert!(selection.candidates.iter().all(|c| c.probability == 0.25));
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_command() -> Result<()> {
+        Cli::command().debug_assert();
+        command().debug_assert();
+        let parse = |args: &[&str]| {
+            let args = args.iter().map(OsString::from);
+            Ok::<_, clap::Error>(Cli::try_parse_args(with_default_command(args))?.command)
+        };
+        let Command::Choose(args) = parse(&["nextspeaker", "p.txt", "--count", "2"])? else {
+            panic!("not choosing");
+        };
+        assert_eq!(args.files.participants, PathBuf::from("p.txt"));
+        assert_eq!(args.count, Some(2));
+        assert!(matches!(
+            parse(&["nextspeaker", "--history", "h.txt", "p.txt"])?,
+            Command::Choose(_)
+        ));
+        assert!(matches!(
+            parse(&["nextspeaker", "simulate", "p.txt", "--draws", "10"])?,
+            Command::Simulate(_)
+        ));
+        assert!(matches!(
+            parse(&["nextspeaker", "rename", "p.txt", "Bob", "Robert"])?,
+            Command::Rename(_)
+        ));
+        assert!(parse(&["nextspeaker", "p.txt", "--group-size", "0"]).is_err());
+        assert!(parse(&[
+            "nextspeaker",
+            "simulate",
+            "p.txt",
+            "--meetings",
+            "5",
+            "--runs",
+            "0"
+        ])
+        .is_err());
+        assert!(parse(&["nextspeaker", "simulate", "p.txt", "--meetings", "0"]).is_err());
+        // Only one way of choosing at a time.
+        let conflicting: [&[&str]; 5] = [
+            &["--count", "2", "--order"],
+            &["--count", "2", "--explain"],
+            &["--roles", "a,b", "--count", "3"],
+            &["--pairs", "--group-size", "3"],
+            &["--order", "--interactive"],
+        ];
+        for args in conflicting {
+            assert!(parse(&[&["nextspeaker", "p.txt"], args].concat()).is_err());
+        }
+        for mode in ["--order", "--pairs", "--explain", "--interactive"] {
+            assert!(parse(&["nextspeaker", "p.txt", mode]).is_ok());
+        }
+        // A switch turned off counts as left out.
+        let Command::Choose(args) = parse(&[
+            "nextspeaker",
+            "p.txt",
+            "--interactive=false",
+            "--count",
+            "2",
+        ])?
+        else {
+            panic!("not choosing");
+        };
+        assert!(!args.interactive);
+        assert!(parse(&["nextspeaker", "p.txt", "--pairs=false", "--group-size", "3"]).is_ok());
+        let untrimmed: [&[&str]; 3] = [
+            &["p.txt", "--record"],
+            &["p.txt", "--max-gap-days", "1"],
+            &["record", "p.txt", "Alice"],
+        ];
+        for args in untrimmed {
+            let args = [&["nextspeaker"], args, &["--history", "h.txt"]].concat();
+            assert!(parse(&[&args[..], &["--history-trim"]].concat()).is_ok());
+            assert!(parse(&[&args[..], &["--history-trim=true"]].concat()).is_ok());
+            assert!(parse(&[&args[..], &["--history-trim=false"]].concat()).is_err());
+        }
+        // Past "--", arguments are left as they are.
+        let Command::Rename(args) = parse(&[
+            "nextspeaker",
+            "rename",
+            "p.txt",
+            "--",
+            "--history-trim=false",
+            "x",
+        ])?
+        else {
+            panic!("not renaming");
+        };
+        assert_eq!(args.old, "--history-trim=false");
+        // Options belong to the commands that use them.
+        assert!(parse(&["nextspeaker", "validate", "p.txt", "--count", "2"]).is_err());
+        // Tuning searches over these itself.
+        assert!(parse(&["nextspeaker", "tune", "p.txt", "--history-halflife", "5"]).is_err());
+        assert!(parse(&["nextspeaker", "tune", "p.txt", "--recent-window", "2"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_gap() -> Result<()> {
+        let participants = "abcde".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let history = ["d", "a", "b", "c", "a", "b", "c"].map(Entry::new);
+        let p = |participants: &[String], max_gap| {
+            let options = Options {
+                max_gap: Some(MaxGap::Entries(max_gap)),
+                ..Default::default()
+            };
+            probabilities(participants, &history, &options)
+        };
+        assert_eq!(p(&participants[..4], 6)?, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(
+            p(&participants[..4], 7)?,
+            probabilities(&participants[..4], &history, &Options::default())?
+        );
+        // A newcomer has waited since the start of the history.
+        let both = p(&participants, 6)?;
+        assert_eq!(both[..3], [0.0, 0.0, 0.0]);
+        assert!(both[3] > 0.0 && both[4] > 0.0);
+        // Being overdue overrides being recent.
+        let options = Options {
+            recent_window: RecentWindow::Entries(5),
+            max_gap: Some(MaxGap::Entries(2)),
+            ..Default::default()
+        };
+        let selection = choose_explained(&participants[..3], &history, &options)?;
+        assert_eq!(selection.chosen, "a");
+        assert!(selection.candidates[0].recent && selection.candidates[0].overdue);
+
+        use chrono::TimeZone;
+        let day = |d| Utc.with_ymd_and_hms(2026, 1, d, 12, 0, 0).single();
+        let history = [
+            Entry {
+                timestamp: day(1),
+                ..Entry::new("a")
+            },
+            Entry {
+                timestamp: day(10),
+                ..Entry::new("b")
+            },
+        ];
+        let options = Options {
+            max_gap: Some(MaxGap::Days {
+                days: 7.0,
+                now: day(15).unwrap(),
+            }),
+            ..Default::default()
+        };
+        let selection = choose_explained(&participants[..3], &history, &options)?;
+        // Round robin favors c, whose turn has no time, over a, who is overdue by days.
+        let round_robin = [
+            Entry::new("c"),
+            history[0].clone(),
+            Entry {
+                timestamp: day(12),
+                ..Entry::new("b")
+            },
+        ];
+        let round_robin_options = Options {
+            strategy: StrategyName::RoundRobin.strategy(),
+            ..options.clone()
+        };
+        assert_eq!(
+            probabilities(&participants[..3], &round_robin, &round_robin_options)?,
+            [1.0, 0.0, 0.0]
+        );
+        let rng = &mut ChaCha12Rng::seed_from_u64(0);
+        assert_eq!(
+            choose_with_rng(&participants[..3], &round_robin, &round_robin_options, rng)?,
+            "a"
+        );
+        let overdue = selection
+            .candidates
+            .iter()
+            .map(|c| c.overdue)
+            .collect::<Vec<_>>();
+        assert_eq!(overdue, [true, false, true]);
+        assert_ne!(selection.chosen, "b");
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalized_names() -> Result<()> {
+        let participants = ["Alice", "Bob"].map(String::from);
+        let history = [
+            Entry::new(" ALICE "),
+            Entry::new("bob"),
+            Entry::new("alice"),
+        ];
+        let options = Options {
+            recent_window: RecentWindow::Entries(1),
+            ..Default::default()
+        };
+        // However the history writes her name, Alice was chosen last.
+        let rng = &mut ChaCha12Rng::seed_from_u64(0);
+        for _ in 0..N_REPS {
+            assert_eq!(
+                choose_with_rng(&participants, &history, &options, rng)?,
+                "Bob"
+            );
+        }
+        let index = HistoryIndex::new(&history, &options)?;
+        assert_eq!(index.tally("Alice").since_last, Some(0));
+        assert_eq!(index.tally("Bob").since_last, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_recent_window() -> Result<()> {
+        let participants = "abcdefgh"
+            .chars()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        let history = entries(&participants);
+        let mut options = Options {
+            recent_window: "6".parse()?,
+            ..Default::default()
+        };
+        for _ in 0..N_REPS {
+            let selection = choose_explained(&participants, &history, &options)?;
+            assert_eq!(selection.recent_window, 6);
+            assert!(["a", "b"].contains(&selection.chosen.as_str()));
+        }
+        options.recent_window = "none".parse()?;
+        let selection = choose_explained(&participants, &history, &options)?;
+        assert_eq!(selection.recent_window, 0);
+        assert!(selection.candidates.iter().all(|c| !c.excluded));
+        options.recent_window = RecentWindow::Entries(6);
+        options.recent_penalty = Some(0.5);
+        let selection = choose_explained(&participants, &history, &options)?;
+        for c in &selection.candidates {
+            assert_eq!(c.recent, !["a", "b"].contains(&c.name.as_str()));
+            assert!(!c.excluded);
+            assert!(c.probability > 0.0);
+        }
+        // With everyone recent, no one is penalized, however hard.
+        options.recent_window = RecentWindow::Entries(8);
+        options.recent_penalty = Some(0.0);
+        let selection = choose_explained(&participants, &history, &options)?;
+        assert!(selection.candidates.iter().all(|c| c.probability > 0.0));
+        assert!(parse_recent_penalty("0").is_err());
+        assert!(parse_recent_penalty("1.5").is_err());
+        assert_eq!(parse_recent_penalty("1"), Ok(1.0));
+        // Declines don't widen the automatic window.
+        let declines = [Entry::new("a")]
+            .into_iter()
+            .chain((0..15).map(|_| Entry::parse("b [declined]", false).unwrap()))
+            .collect::<Vec<_>>();
+        let selection = choose_explained(&participants, &declines, &Options::default())?;
+        assert_eq!(selection.recent_window, 0);
+        assert!("sometimes".parse::<RecentWindow>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_probabilities() -> Result<()> {
+        let participants = "abcdef".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let history = entries(&["a", "a", "b", "c", "a", "d"].map(String::from));
+        let options = Options {
+            recent_window: RecentWindow::Entries(2),
+            recent_penalty: Some(0.5),
+            ..Default::default()
+        };
+        let exact = probabilities(&participants, &history, &options)?;
+        assert!((exact.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        let rng = &mut ChaCha12Rng::seed_from_u64(0);
+        let n_draws = 20 * N_REPS;
+        let mut counts = HashMap::new();
+        for _ in 0..n_draws {
+            let choice = choose_with_rng(&participants, &history, &options, rng)?;
+            *counts.entry(choice).or_insert(0_usize) += 1;
+        }
+        for (name, p) in participants.iter().zip(exact) {
+            let observed = *counts.get(name).unwrap_or(&0) as f64 / n_draws as f64;
+            assert!((observed - p).abs() < 0.015, "{name}: {observed} vs {p}");
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_recent() -> Result<()> {
-        let args = &Args::dummy();
+        let args = &SelectionArgs::dummy();
         let participants = "abcdefghijklmnopqrstuvwxyz"
             .chars()
             .map(|c| c.to_string())
             .collect::<Vec<_>>();
-        let history = participants.clone();
+        let history = entries(&participants);
         for _ in 0..N_REPS {
-            let choice = choose(&participants, &history, args.history_halflife)?;
+            let choice = choose(
+                &participants,
+                &history,
+                &args.options(&TunableArgs::default()),
+            )?;
             for name in ["x", "y", "z"] {
                 assert_ne!(choice, name);
             }
@@ -297,20 +1658,92 @@ mod test {
         Ok(())
     }
 
-    impl Args {
+    #[test]
+    fn test_declines() -> Result<()> {
+        let participants = "abc".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let history = history::parse(
+            &["a", "b", "c", "a [declined]", "b [skipped]"].map(String::from),
+            false,
+        )?;
+        let p = |decline_weight| {
+            let options = Options {
+                decline_weight,
+                recent_window: RecentWindow::None,
+                ..Default::default()
+            };
+            probabilities(&participants, &history, &options)
+        };
+        // Owing a turn favors a over c, counting half a turn disfavors it,
+        // and a skip leaves b even with c.
+        let owed = p(-1.0)?;
+        assert!(owed[0] > owed[2], "{owed:?}");
+        let half = p(0.5)?;
+        assert!(half[0] < half[2], "{half:?}");
+        let ignored = p(0.0)?;
+        assert!((ignored[1] - ignored[2]).abs() < 0.02, "{ignored:?}");
+        // Declines and skips neither make participants recent
+        // nor push those who spoke out of the recent window.
+        let options = Options {
+            recent_window: RecentWindow::Entries(2),
+            ..Default::default()
+        };
+        let selection = choose_explained(&participants, &history, &options)?;
+        let recent = selection
+            .candidates
+            .iter()
+            .map(|c| c.recent)
+            .collect::<Vec<_>>();
+        assert_eq!(recent, [false, true, true]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_roles() -> Result<()> {
+        let participants = "abcd".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let lines = [
+            "a [role=facilitator]",
+            "b [role=facilitator]",
+            "c [role=notes]",
+        ];
+        let history = history::parse(&lines.map(String::from), false)?;
+        let roles = ["facilitator", "notes"]
+            .map(|role| role.parse())
+            .into_iter()
+            .collect::<Result<Vec<Role>>>()?;
+        let options = Options {
+            recent_window: RecentWindow::None,
+            strategy: StrategyName::RoundRobin.strategy(),
+            ..Default::default()
+        };
+        let mut notes = HashMap::new();
+        for _ in 0..N_REPS {
+            let chosen = choose_roles(&participants, &history, &roles, &options)?;
+            assert_ne!(chosen[0], chosen[1]);
+            assert!(["c", "d"].contains(&chosen[0].as_str()), "{chosen:?}");
+            *notes.entry(chosen[1].clone()).or_insert(0) += 1;
+        }
+        // Facilitating doesn't count against taking notes.
+        assert!(!notes.contains_key("c"));
+        assert!(notes.contains_key("a") && notes.contains_key("b"));
+        assert!(choose_roles(&participants[..1], &history, &roles, &options).is_err());
+        Ok(())
+    }
+
+    impl SelectionArgs {
         pub fn dummy() -> Self {
             Self {
-                participants: PathBuf::from("dummy"),
-                history: Some(PathBuf::from("dummy-history")),
-                history_halflife: 10.0,
-                history_trim: false,
-                n_simulations: None,
+                strategy: StrategyName::BetaDecay,
+                recent_penalty: None,
+                max_gap: None,
+                max_gap_days: None,
+                decline_weight: DEFAULT_DECLINE_WEIGHT,
+                cross_role_weight: 0.0,
+                absent: vec![],
+                present_only: None,
+                availability: None,
+                date: None,
+                seed: None,
             }
         }
-        pub fn dummy_with_halflife(halflife: f64) -> Self {
-            let mut args = Self::dummy();
-            args.history_halflife = halflife;
-            args
-        }
     }
 }
@@ -1,82 +1,618 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead},
+    path::Path,
+};
 
 use anyhow::{anyhow, bail, Context, Result};
-use clap::Parser;
-use log::info;
-use regex::Regex;
+use chrono::{TimeDelta, Utc};
+use log::{info, warn};
 
-use nextspeaker::{choose, Args};
+use nextspeaker::{
+    availability::{Absence, Availability},
+    choose_explained_with_rng, choose_from_index, choose_many_with_rng, choose_roles_with_rng,
+    choose_with_rng,
+    fairness::{self, Fairness},
+    grouping::{choose_groups_with_rng, choose_pairs_with_rng},
+    history::{self, Entry, HistoryIndex, Outcome},
+    interactive::{Key, Session},
+    order_with_rng, probabilities,
+    record::{self, HistoryFile},
+    roster::Roster,
+    simulate::{simulate_meetings_with_rng, Parity, Summary},
+    tune::{tune_with_rng, Tuning},
+    validate, AmendArgs, ChooseArgs, Cli, Command, Files, RecordArgs, RenameArgs, Selection,
+    SelectionArgs, SimulateArgs, StatsArgs, TunableArgs, TuneArgs, UndoArgs,
+};
 
 fn non_blanks_nor_comments(path: &Path) -> Result<Vec<String>> {
     let content = fs::read_to_string(path).with_context(|| format!("reading {path:?}"))?;
-    Ok(content
+    Ok(lines_of(&content))
+}
+
+fn lines_of(content: &str) -> Vec<String> {
+    content
         .lines()
         .filter(|i| !i.is_empty() && !i.starts_with('#'))
         .map(|s| s.to_string())
-        .collect())
+        .collect()
 }
 
-fn maybe_trim_history(history: Vec<String>, do_it: bool) -> Result<Vec<String>> {
-    if !do_it {
-        return Ok(history);
+/// Reports likely mistakes in the participant list and history,
+/// failing if there are any
+fn validate_files(files: &Files) -> Result<()> {
+    let read = |path: &Path| fs::read_to_string(path).with_context(|| format!("reading {path:?}"));
+    let participants = read(&files.participants)?;
+    let history = match &files.history {
+        Some(path) => read(path)?,
+        None => String::new(),
+    };
+    let problems = validate::validate(&participants, &history, files.history_trim);
+    for problem in &problems {
+        let path = match (problem.in_participants(), &files.history) {
+            (false, Some(path)) => path,
+            _ => &files.participants,
+        };
+        println!("{}:{}: {problem}", path.display(), problem.line());
+    }
+    if !problems.is_empty() {
+        bail!("found {} problems", problems.len());
     }
-    let re = Regex::new(r"^\S+\s+(.*)$").unwrap();
-    Ok(history
-        .into_iter()
-        .map(|line| match re.captures(&line) {
-            None => bail!("cannot trim history from line: {}", &line),
-            Some(groups) => Ok(groups[1].to_string()),
-        })
-        .collect::<Result<Vec<_>>>()?)
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    simple_logger::init_with_env().context("initializing logger")?;
-    let args = Args::parse();
+fn print_explanation(selection: &Selection, recent_penalty: Option<f64>) {
+    let plen = selection
+        .candidates
+        .iter()
+        .map(|c| c.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("participant".len());
+    println!(
+        "  {:<plen$} {:>8} {:>16} {:>7} {:>8} {:>11}",
+        "participant", "history", "beta", "weight", "recent", "probability"
+    );
+    for c in &selection.candidates {
+        let beta = match c.beta {
+            Some((alpha, beta)) => format!("({alpha:.2}, {beta:.2})"),
+            None => "-".to_string(),
+        };
+        println!(
+            "{} {:<plen$} {:>8.3} {:>16} {:>7.3} {:>8} {:>10.1}%",
+            if c.name == selection.chosen { '*' } else { ' ' },
+            c.name,
+            c.history_weight,
+            beta,
+            c.weight,
+            match (c.excluded, c.recent, recent_penalty) {
+                (true, _, _) => "excluded".to_string(),
+                (false, true, Some(penalty)) => format!("x{penalty}"),
+                (false, true, None) => "yes".to_string(),
+                (false, false, _) => "".to_string(),
+            },
+            100.0 * c.probability,
+        );
+    }
+    let overdue = selection
+        .candidates
+        .iter()
+        .filter(|c| c.overdue)
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>();
+    if !overdue.is_empty() {
+        println!(
+            "overdue, so chosen over everyone else: {}",
+            overdue.join(", ")
+        );
+    }
+}
 
-    let participants = non_blanks_nor_comments(&args.participants)?;
-    let history = maybe_trim_history(
-        if let Some(hist_path) = &args.history {
-            non_blanks_nor_comments(hist_path)?
-        } else {
-            vec![]
-        },
-        args.history_trim,
+fn print_summary(summary: &Summary) {
+    let plen = summary
+        .participants
+        .iter()
+        .map(|p| p.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("participant".len());
+    println!("{} meetings, {} runs", summary.meetings, summary.runs);
+    println!(
+        "{:<plen$} {:>7} {:>10} {:>7} {:>15} {:>14}",
+        "participant", "turns", "median gap", "90% gap", "longest wait", "parity"
+    );
+    let gap = |gap: Option<usize>| gap.map_or("-".to_string(), |g| g.to_string());
+    for p in &summary.participants {
+        let parity = match &p.parity {
+            None => "-".to_string(),
+            Some(Parity {
+                mean_meetings: Some(meetings),
+                reached,
+            }) => format!("{meetings:.1} ({:.0}%)", 100.0 * reached),
+            Some(Parity {
+                mean_meetings: None,
+                ..
+            }) => "never".to_string(),
+        };
+        println!(
+            "{:<plen$} {:>7.2} {:>10} {:>7} {:>15} {:>14}",
+            p.name,
+            p.mean_turns,
+            gap(p.median_gap),
+            gap(p.p90_gap),
+            format!("{:.1} (max {})", p.mean_longest_wait, p.longest_wait),
+            parity,
+        );
+    }
+}
+
+fn print_fairness(fairness: &Fairness, json: bool) -> Result<()> {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(fairness).context("writing JSON")?
+        );
+        return Ok(());
+    }
+    let plen = fairness
+        .participants
+        .iter()
+        .map(|p| p.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("participant".len());
+    match fairness.runs {
+        1 => println!("{} meetings", fairness.meetings),
+        runs => println!("{} meetings, {runs} runs", fairness.meetings),
+    }
+    println!("gini coefficient: {:.3}", fairness.gini);
+    match fairness.max_min_ratio {
+        Some(ratio) => println!("max/min turn ratio: {ratio:.2}"),
+        None => println!("max/min turn ratio: undefined, since someone had no turns"),
+    }
+    println!(
+        "coefficient of variation: {:.3}",
+        fairness.coefficient_of_variation
+    );
+    println!(
+        "{:<plen$} {:>7} {:>11} {:>13}",
+        "participant", "turns", "longest gap", "expected wait"
+    );
+    for p in &fairness.participants {
+        println!(
+            "{:<plen$} {:>7.2} {:>11} {:>13}",
+            p.name,
+            p.turns,
+            p.longest_gap,
+            p.expected_wait
+                .map_or("-".to_string(), |wait| format!("{wait:.1}")),
+        );
+    }
+    Ok(())
+}
+
+fn print_tuning(tuning: &Tuning, max_wait: usize) {
+    println!(
+        "  {:>8} {:>13} {:>12} {:>16} {:>11}",
+        "halflife", "recent window", "longest wait", "unpredictability", "meets target"
+    );
+    for (i, trial) in tuning.trials.iter().enumerate() {
+        println!(
+            "{} {:>8} {:>13} {:>12} {:>16.2} {:>11}",
+            if i == tuning.best { '*' } else { ' ' },
+            trial.halflife,
+            trial.recent_window.to_string(),
+            trial.longest_wait,
+            trial.unpredictability,
+            if trial.meets_target { "yes" } else { "no" },
+        );
+    }
+    let best = &tuning.trials[tuning.best];
+    if !best.meets_target {
+        warn!("no setting keeps the longest wait within {max_wait} meetings");
+    }
+    println!(
+        "recommended: --history-halflife {} --recent-window {}",
+        best.halflife, best.recent_window
+    );
+}
+
+/// The roster, history, and participants present, as read for a command
+struct Meeting {
+    roster: Roster,
+    history_file: Option<HistoryFile>,
+    history: Vec<Entry>,
+    participants: Vec<String>,
+}
+
+fn read_roster(files: &Files) -> Result<Roster> {
+    Roster::parse(&non_blanks_nor_comments(&files.participants)?).context("processing participants")
+}
+
+fn read_meeting(files: &Files, selection: &SelectionArgs) -> Result<Meeting> {
+    let roster = read_roster(files)?;
+    let history_file = match &files.history {
+        Some(hist_path) => Some(HistoryFile::read(hist_path)?),
+        None => None,
+    };
+    let mut history = history::parse(
+        &history_file
+            .as_ref()
+            .map_or(vec![], |file| lines_of(file.content())),
+        files.history_trim,
     )
     .context("processing history")?;
+    let unmatched = roster.canonicalize(&mut history);
+    if !unmatched.is_empty() {
+        warn!("history names matching no participant: {unmatched:?}");
+    }
 
-    if participants.is_empty() {
+    if roster.participants().is_empty() {
         return Err(anyhow!("participant list is empty"));
     }
-    if let Some(n_simulations) = args.n_simulations {
-        let mut counts: HashMap<_, _> = HashMap::new();
-        for _ in 0..n_simulations {
-            let selection = choose(&participants, &history, args.history_halflife)
-                .context("choosing participant")?;
-            counts
-                .entry(selection)
-                .and_modify(|count| *count += 1)
-                .or_insert(1);
-        }
-        let plen = participants
+    let availability = Availability {
+        absent: selection
+            .absent
             .iter()
-            .map(|p| p.chars().map(|_| 1).sum::<usize>())
-            .max()
-            .ok_or_else(|| anyhow!("cannot get maximum-length participant name"))?;
-        for p in participants {
-            let count = counts.get(&p);
-            println!(
-                "{:>width$}: {}",
-                p,
-                if let Some(n) = count { *n } else { 0 },
-                width = plen + 1
-            );
+            .map(|name| roster.canonical_or_same(name))
+            .collect(),
+        present_only: match &selection.present_only {
+            Some(path) => Some(
+                non_blanks_nor_comments(path)?
+                    .iter()
+                    .map(|name| roster.canonical_or_same(name))
+                    .collect(),
+            ),
+            None => None,
+        },
+        absences: match &selection.availability {
+            Some(path) => non_blanks_nor_comments(path)?
+                .iter()
+                .map(|line| {
+                    let absence = Absence::parse(line)?;
+                    Ok(Absence {
+                        name: roster.canonical_or_same(&absence.name),
+                        ..absence
+                    })
+                })
+                .collect::<Result<_>>()
+                .context("processing availability")?,
+            None => vec![],
+        },
+    };
+    let participants = availability.present(roster.participants(), selection.meeting_date());
+    if participants.is_empty() {
+        return Err(anyhow!("no participants are present"));
+    }
+    Ok(Meeting {
+        roster,
+        history_file,
+        history,
+        participants,
+    })
+}
+
+fn choose(args: &ChooseArgs) -> Result<()> {
+    let Meeting {
+        roster,
+        mut history_file,
+        mut history,
+        mut participants,
+    } = read_meeting(&args.files, &args.selection)?;
+    let options = args.selection.options(&args.tunable);
+    let rng = &mut args.selection.rng();
+    if let (Some(name), Some(file)) = (&args.decline, &mut history_file) {
+        let name = &roster.canonical_or_same(name);
+        if !participants.contains(name) {
+            bail!("{name} is not a present participant");
+        }
+        let entry = Entry {
+            timestamp: Some(Utc::now()),
+            outcome: Outcome::Declined,
+            ..Entry::new(name)
+        };
+        file.append(&[entry.to_line(args.files.history_trim)])
+            .context("recording decline")?;
+        info!("declined:{name}");
+        history.push(entry);
+        participants.retain(|p| p != name);
+        if participants.is_empty() {
+            bail!("no other participants are present");
+        }
+    }
+    if args.interactive {
+        let mut session = Session::new(&participants, &history, &options, args.reroll_declines);
+        let mut input = io::stdin().lock();
+        session.press(Key::Next, rng)?;
+        loop {
+            match (session.pick(), session.remaining().is_empty()) {
+                (Some(pick), _) => println!("{pick}  [a]ccept, [r]eroll, [s]kip, [n]ext, [q]uit"),
+                (None, false) => println!("[n]ext, [q]uit"),
+                (None, true) => println!("everyone has had a turn  [q]uit"),
+            }
+            let mut line = String::new();
+            if input.read_line(&mut line).context("reading key")? == 0 {
+                return Ok(());
+            }
+            let key = match line.parse::<Key>() {
+                Ok(Key::Quit) => return Ok(()),
+                Ok(key) => key,
+                Err(err) => {
+                    warn!("{err}");
+                    continue;
+                }
+            };
+            let entry = match session.press(key, rng) {
+                Ok(entry) => entry,
+                Err(err) => {
+                    warn!("{err}");
+                    continue;
+                }
+            };
+            if let Some(entry) = entry {
+                info!("{}", entry);
+                if let (true, Some(file)) = (args.record, &mut history_file) {
+                    file.append(&[entry.to_line(true)]).context("recording")?;
+                }
+            }
         }
+    }
+    let mut recorded = vec![];
+    if !args.roles.is_empty() {
+        let chosen = choose_roles_with_rng(&participants, &history, &args.roles, &options, rng)
+            .context("assigning roles")?;
+        for (role, name) in args.roles.iter().zip(chosen) {
+            info!("role:{role} selection:{name}");
+            println!("{role}: {name}");
+            recorded.push(Entry {
+                role: Some(role.clone()),
+                ..Entry::new(&name)
+            });
+        }
+    } else if args.pairs {
+        let pairs = choose_pairs_with_rng(&participants, &history, &options, rng)
+            .context("choosing pairs")?;
+        info!("pairs:{pairs:?}");
+        let entry = Entry::grouping(&pairs);
+        println!("{entry}");
+        recorded.push(entry);
+    } else if let Some(size) = args.group_size {
+        let groups = choose_groups_with_rng(&participants, &history, size, &options, rng)
+            .context("choosing groups")?;
+        info!("groups:{groups:?}");
+        let entry = Entry::grouping(&groups);
+        println!("{entry}");
+        recorded.push(entry);
+    } else if args.order {
+        let speakers = order_with_rng(&participants, &history, &options, rng)
+            .context("ordering participants")?;
+        info!("order:{speakers:?}");
+        let line = history::format_order(&speakers);
+        println!("{line}");
+        recorded.push(Entry::new(&line));
+    } else if let Some(count) = args.count {
+        let selections = choose_many_with_rng(&participants, &history, &options, count, rng)
+            .context("choosing participants")?;
+        info!("selections:{selections:?}");
+        for selection in selections {
+            println!("{}", selection);
+            recorded.push(Entry::new(&selection));
+        }
+    } else if args.explain {
+        let selection = choose_explained_with_rng(&participants, &history, &options, rng)
+            .context("choosing participant")?;
+        info!("selection:{}", &selection.chosen);
+        println!("recent window: {} entries", selection.recent_window);
+        print_explanation(&selection, options.recent_penalty);
+        println!("{}", selection.chosen);
+        recorded.push(Entry::new(&selection.chosen));
     } else {
-        let selection = choose(&participants, &history, args.history_halflife)
+        let selection = choose_with_rng(&participants, &history, &options, rng)
             .context("choosing participant")?;
         info!("selection:{}", &selection);
         println!("{}", selection);
+        recorded.push(Entry::new(&selection));
+    }
+    if let (true, Some(file)) = (args.record, &mut history_file) {
+        let now = Utc::now();
+        let lines = recorded
+            .into_iter()
+            .map(|entry| {
+                Entry {
+                    timestamp: Some(now),
+                    ..entry
+                }
+                .to_line(true)
+            })
+            .collect::<Vec<_>>();
+        file.append(&lines).context("recording")?;
     }
     Ok(())
 }
+
+fn simulate(args: &SimulateArgs) -> Result<()> {
+    let Meeting {
+        history,
+        participants,
+        ..
+    } = read_meeting(&args.files, &args.selection)?;
+    let options = args.selection.options(&args.tunable);
+    let rng = &mut args.selection.rng();
+    if let Some(meetings) = args.meetings {
+        let runs =
+            simulate_meetings_with_rng(&participants, &history, &options, meetings, args.runs, rng)
+                .context("simulating meetings")?;
+        if args.fairness {
+            print_fairness(&Fairness::of_runs(&participants, &runs), args.json)?;
+        } else {
+            let summary = Summary::new(&participants, &history, &options, &runs)
+                .context("summarizing simulation")?;
+            print_summary(&summary);
+        }
+        return Ok(());
+    }
+    let Some(draws) = args.draws else {
+        bail!("simulating needs --meetings or --draws");
+    };
+    let index = HistoryIndex::new(&history, &options).context("indexing history")?;
+    let mut counts: HashMap<_, _> = HashMap::new();
+    for _ in 0..draws {
+        let selection = choose_from_index(&participants, &index, &options, rng)
+            .context("choosing participant")?
+            .chosen;
+        counts
+            .entry(selection)
+            .and_modify(|count| *count += 1)
+            .or_insert(1);
+    }
+    let plen = participants
+        .iter()
+        .map(|p| p.chars().map(|_| 1).sum::<usize>())
+        .max()
+        .ok_or_else(|| anyhow!("cannot get maximum-length participant name"))?;
+    for p in participants {
+        let count = counts.get(&p);
+        println!(
+            "{:>width$}: {}",
+            p,
+            if let Some(n) = count { *n } else { 0 },
+            width = plen + 1
+        );
+    }
+    Ok(())
+}
+
+fn tune(args: &TuneArgs) -> Result<()> {
+    let Meeting {
+        history,
+        participants,
+        ..
+    } = read_meeting(&args.files, &args.selection)?;
+    let options = args.selection.options(&TunableArgs::default());
+    let rng = &mut args.selection.rng();
+    let meetings = args.meetings.unwrap_or(4 * participants.len());
+    let max_wait = (args.max_wait_factor * participants.len() as f64).round() as usize;
+    let tuning = tune_with_rng(
+        &participants,
+        &history,
+        &options,
+        max_wait,
+        meetings,
+        args.runs,
+        rng,
+    )
+    .context("tuning")?;
+    print_tuning(&tuning, max_wait);
+    Ok(())
+}
+
+fn stats(args: &StatsArgs) -> Result<()> {
+    let Meeting {
+        roster,
+        history,
+        participants,
+        ..
+    } = read_meeting(&args.files, &args.selection)?;
+    if !args.probabilities {
+        let turns = fairness::turns(&history);
+        return print_fairness(&Fairness::new(roster.participants(), &turns), args.json);
+    }
+    let probabilities = probabilities(
+        &participants,
+        &history,
+        &args.selection.options(&args.tunable),
+    )
+    .context("computing probabilities")?;
+    let plen = participants
+        .iter()
+        .map(|p| p.chars().count())
+        .max()
+        .ok_or_else(|| anyhow!("cannot get maximum-length participant name"))?;
+    for (p, probability) in participants.iter().zip(probabilities) {
+        println!(
+            "{:>width$}: {:.2}%",
+            p,
+            100.0 * probability,
+            width = plen + 1
+        );
+    }
+    Ok(())
+}
+
+fn record_turn(args: &RecordArgs) -> Result<()> {
+    let roster = read_roster(&args.files)?;
+    let Some(name) = roster.canonical(&args.name) else {
+        bail!("{} is not a participant", args.name);
+    };
+    let entry = Entry {
+        timestamp: Some(Utc::now()),
+        ..Entry::new(name)
+    };
+    record::append(args.files.history_path()?, &[entry.to_line(true)]).context("recording")?;
+    info!("recorded:{name}");
+    Ok(())
+}
+
+fn undo(args: &UndoArgs) -> Result<()> {
+    let since = args
+        .within
+        .map(|minutes| Utc::now() - TimeDelta::minutes(minutes as i64));
+    let mut removed = String::new();
+    record::edit(args.files.history_path()?, |content| {
+        let (edited, line) = record::undo(content, since)?;
+        removed = line;
+        Ok(edited)
+    })
+    .context("undoing")?;
+    println!("removed: {removed}");
+    Ok(())
+}
+
+fn amend(args: &AmendArgs) -> Result<()> {
+    let roster = read_roster(&args.files)?;
+    let Some(name) = roster.canonical(&args.name) else {
+        bail!("{} is not a participant", args.name);
+    };
+    let mut replaced = String::new();
+    record::edit(args.files.history_path()?, |content| {
+        let (edited, line) = record::amend(content, name, args.files.history_trim)?;
+        replaced = line;
+        Ok(edited)
+    })
+    .context("amending")?;
+    println!("amended: {replaced}");
+    Ok(())
+}
+
+fn rename(args: &RenameArgs) -> Result<()> {
+    let roster = read_roster(&args.files)?;
+    let (old, new) = (&args.old, &args.new);
+    if roster.canonical(new).is_none() {
+        warn!("{new} is not a participant");
+    }
+    let mut changed = 0;
+    record::edit(args.files.history_path()?, |content| {
+        let edited;
+        (edited, changed) = record::rename(content, old, new, args.files.history_trim)?;
+        Ok(edited)
+    })
+    .context("renaming")?;
+    println!("renamed {old} to {new} in {changed} entries");
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    simple_logger::init_with_env().context("initializing logger")?;
+    match &Cli::parse_with_default_command()?.command {
+        Command::Choose(args) => choose(args),
+        Command::Simulate(args) => simulate(args),
+        Command::Tune(args) => tune(args),
+        Command::Stats(args) => stats(args),
+        Command::Validate(files) => validate_files(files),
+        Command::Record(args) => record_turn(args),
+        Command::Undo(args) => undo(args),
+        Command::Amend(args) => amend(args),
+        Command::Rename(args) => rename(args),
+    }
+}
@@ -0,0 +1,185 @@
+//! Exact selection probabilities by numerical integration
+//!
+//! A participant with weight w_i is chosen with probability w_i / S,
+//! where S is the sum of all the weights.
+//! Since 1/S is the integral of exp(-tS) over t from zero to infinity,
+//! and the weights are independent,
+//! the chance of choosing participant i is the integral over t of
+//! E[w_i exp(-t w_i)] times the product over j != i of E[exp(-t w_j)].
+//! The expectations and the integral over t are computed with
+//! double exponential quadrature, which copes with the endpoints
+//! of Beta densities and with the infinite range of t.
+
+use std::f64::consts::FRAC_PI_2;
+
+use crate::strategy::Weight;
+
+/// The spacing of quadrature nodes in the transformed variable
+const STEP: f64 = 1.0 / 16.0;
+/// The transformed variable ranges over [-LIMIT, LIMIT],
+/// beyond which node weights are negligible
+const LIMIT: f64 = 4.0;
+
+/// A quadrature node on the unit interval,
+/// keeping `1 - x` separately for precision near one
+struct UnitNode {
+    x: f64,
+    one_minus_x: f64,
+    weight: f64,
+}
+
+/// Nodes for integrating over [0, 1] with the tanh-sinh rule
+fn unit_nodes() -> Vec<UnitNode> {
+    let n = (LIMIT / STEP) as i32;
+    (-n..=n)
+        .map(|k| {
+            let tau = k as f64 * STEP;
+            let y = FRAC_PI_2 * tau.sinh();
+            let e = (-2.0 * y.abs()).exp();
+            let (near, far) = (e / (1.0 + e), 1.0 / (1.0 + e));
+            let (x, one_minus_x) = if y < 0.0 { (near, far) } else { (far, near) };
+            // dx/dtau is half of sech^2(y) times dy/dtau.
+            let sech2 = 4.0 * e / ((1.0 + e) * (1.0 + e));
+            UnitNode {
+                x,
+                one_minus_x,
+                weight: STEP * 0.5 * sech2 * FRAC_PI_2 * tau.cosh(),
+            }
+        })
+        .collect()
+}
+
+/// Nodes and weights for integrating over [0, infinity) with the exp-sinh rule
+fn half_line_nodes() -> Vec<(f64, f64)> {
+    let n = (LIMIT / STEP) as i32;
+    (-n..=n)
+        .map(|k| {
+            let tau = k as f64 * STEP;
+            let t = (FRAC_PI_2 * tau.sinh()).exp();
+            (t, STEP * t * FRAC_PI_2 * tau.cosh())
+        })
+        .collect()
+}
+
+/// A participant's weight distribution, scaled by `factor`,
+/// ready for computing E[exp(-tw)] and E[w exp(-tw)]
+enum Scaled {
+    Fixed(f64),
+    /// Values at the unit nodes, and the probability mass there
+    Sampled(Vec<(f64, f64)>),
+}
+
+impl Scaled {
+    fn new(weight: Weight, factor: f64, nodes: &[UnitNode]) -> Self {
+        match weight {
+            Weight::Fixed(w) => Self::Fixed(w * factor),
+            Weight::Beta { alpha, beta } => {
+                let density = nodes
+                    .iter()
+                    .map(|node| {
+                        let log_density =
+                            (alpha - 1.0) * node.x.ln() + (beta - 1.0) * node.one_minus_x.ln();
+                        node.weight * log_density.exp()
+                    })
+                    .collect::<Vec<_>>();
+                // Normalizing by the quadrature sum stands in for the Beta function.
+                let total = density.iter().sum::<f64>();
+                Self::Sampled(
+                    nodes
+                        .iter()
+                        .zip(density)
+                        .map(|(node, mass)| (factor * node.x, mass / total))
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    /// Returns E[exp(-tw)] and E[w exp(-tw)]
+    fn transforms(&self, t: f64) -> (f64, f64) {
+        match self {
+            Self::Fixed(w) => {
+                let e = (-t * w).exp();
+                (e, w * e)
+            }
+            Self::Sampled(points) => points.iter().fold((0.0, 0.0), |(l, m), (w, mass)| {
+                let e = mass * (-t * w).exp();
+                (l + e, m + w * e)
+            }),
+        }
+    }
+}
+
+/// Returns the chance that a weighted draw picks each participant,
+/// given each participant's weight distribution and a factor
+/// to scale the drawn weight by
+pub fn selection_probabilities(weights: &[(Weight, f64)]) -> Vec<f64> {
+    let fixed = weights
+        .iter()
+        .map(|(weight, factor)| match weight {
+            Weight::Fixed(w) => Some(w * factor),
+            Weight::Beta { .. } => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    let probabilities = if let Some(fixed) = fixed {
+        fixed
+    } else {
+        let nodes = unit_nodes();
+        let scaled = weights
+            .iter()
+            .map(|(weight, factor)| Scaled::new(*weight, *factor, &nodes))
+            .collect::<Vec<_>>();
+        let mut probabilities = vec![0.0; weights.len()];
+        for (t, dt) in half_line_nodes() {
+            let (l, m): (Vec<_>, Vec<_>) = scaled.iter().map(|s| s.transforms(t)).unzip();
+            // Products of the other participants' transforms, from both sides,
+            // avoid dividing by transforms that have underflowed to zero.
+            let mut before = vec![1.0; l.len() + 1];
+            for (i, l_i) in l.iter().enumerate() {
+                before[i + 1] = before[i] * l_i;
+            }
+            let mut after = 1.0;
+            for i in (0..l.len()).rev() {
+                probabilities[i] += dt * m[i] * before[i] * after;
+                after *= l[i];
+            }
+        }
+        probabilities
+    };
+    let total = probabilities.iter().sum::<f64>();
+    if total > 0.0 {
+        probabilities.iter().map(|p| p / total).collect()
+    } else {
+        probabilities
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UNIFORM: Weight = Weight::Beta {
+        alpha: 1.0,
+        beta: 1.0,
+    };
+
+    #[test]
+    fn test_fixed() {
+        let p = selection_probabilities(&[(Weight::Fixed(1.0), 1.0), (Weight::Fixed(3.0), 1.0)]);
+        assert_eq!(p, [0.25, 0.75]);
+    }
+
+    #[test]
+    fn test_uniform_against_fixed() {
+        // E[X / (X + 1)] for X uniform on [0, 1] is 1 - ln 2.
+        let p = selection_probabilities(&[(UNIFORM, 1.0), (Weight::Fixed(1.0), 1.0)]);
+        assert!((p[0] - (1.0 - 2_f64.ln())).abs() < 1e-6, "{p:?}");
+    }
+
+    #[test]
+    fn test_symmetry_and_exclusion() {
+        let p = selection_probabilities(&[(UNIFORM, 1.0), (UNIFORM, 0.0), (UNIFORM, 1.0)]);
+        assert!((p[0] - 0.5).abs() < 1e-6, "{p:?}");
+        assert_eq!(p[1], 0.0);
+    }
+}
@@ -0,0 +1,368 @@
+//! Writing history files that several people may be recording to at once
+
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use regex::Regex;
+
+use crate::{history::Entry, roster::normalize};
+
+/// A leading whitespace-delimited field, with the whitespace after it
+static LEADING_FIELD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\S+\s+").unwrap());
+
+/// A history file as it was read,
+/// so that choices made from it are only recorded if no one else has since
+#[derive(Clone, Debug)]
+pub struct HistoryFile {
+    pub path: PathBuf,
+    content: String,
+}
+
+impl HistoryFile {
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("reading {path:?}"))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            content,
+        })
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Appends lines while holding a lock on the file,
+    /// failing without writing anything if the file has changed since it was read
+    pub fn append(&mut self, lines: &[String]) -> Result<()> {
+        let path = &self.path;
+        let mut file = open_locked(path)?;
+        let mut current = String::new();
+        file.read_to_string(&mut current)
+            .with_context(|| format!("reading {path:?}"))?;
+        if current != self.content {
+            bail!("{path:?} has changed since it was read, so nothing was recorded; try again");
+        }
+        self.content
+            .push_str(&write_lines(&mut file, path, &current, lines)?);
+        Ok(())
+    }
+}
+
+/// Appends lines to a history file, creating it if need be,
+/// while holding a lock on it
+pub fn append(path: &Path, lines: &[String]) -> Result<()> {
+    let mut file = open_locked(path)?;
+    let mut current = String::new();
+    file.read_to_string(&mut current)
+        .with_context(|| format!("reading {path:?}"))?;
+    write_lines(&mut file, path, &current, lines)?;
+    Ok(())
+}
+
+/// Edits a history file while holding a lock on it,
+/// replacing its content with what `edit` makes of it.
+/// The new content is written to a file beside it that then takes its place,
+/// so that a failed write leaves the history as it was.
+pub fn edit(path: &Path, edit: impl FnOnce(&str) -> Result<String>) -> Result<()> {
+    let mut file = open_locked(path)?;
+    let mut current = String::new();
+    file.read_to_string(&mut current)
+        .with_context(|| format!("reading {path:?}"))?;
+    let edited = edit(&current)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{path:?} is not a file"))?;
+    let temp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    let replaced = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp)
+        .and_then(|mut copy| {
+            copy.write_all(edited.as_bytes())?;
+            copy.set_permissions(file.metadata()?.permissions())?;
+            copy.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp, path));
+    if replaced.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    // The lock is held until the new file is in place.
+    drop(file);
+    replaced.with_context(|| format!("writing {path:?}"))
+}
+
+/// Removes the last entry from history content,
+/// returning the new content and the line removed.
+/// With `recorded_since`, the entry must have been recorded with a timestamp since then,
+/// which is read from its leading field.
+pub fn undo(content: &str, recorded_since: Option<DateTime<Utc>>) -> Result<(String, String)> {
+    let mut lines = split_lines(content);
+    let last = last_entry(&lines)?;
+    let (line, _) = lines.remove(last);
+    if let Some(since) = recorded_since {
+        match Entry::parse(line, true)?.timestamp {
+            None => bail!("the last entry has no timestamp: {line}"),
+            Some(timestamp) if timestamp < since => {
+                bail!(
+                    "the last entry was recorded before {}: {line}",
+                    since.to_rfc3339_opts(SecondsFormat::Secs, true)
+                )
+            }
+            Some(_) => {}
+        }
+    }
+    Ok((join_lines(&lines), line.to_string()))
+}
+
+/// Replaces the names in the last entry of history content with `text`,
+/// keeping its leading field, when `trim` is set, and its tags,
+/// and returns the new content and the line replaced
+pub fn amend(content: &str, text: &str, trim: bool) -> Result<(String, String)> {
+    let mut lines = split_lines(content);
+    let last = last_entry(&lines)?;
+    let (line, ending) = lines[last];
+    let (field, rest) = split_field(line, trim)?;
+    let entry = Entry::parse(rest, false)?;
+    if entry.is_grouping() {
+        bail!("the last entry is a grouping, which one name can't replace: {line}");
+    }
+    let entry = Entry {
+        text: text.to_string(),
+        ..entry
+    };
+    let amended = format!("{field}{entry}");
+    lines[last] = (&amended, ending);
+    Ok((join_lines(&lines), line.to_string()))
+}
+
+/// Replaces the name `old`, however it is written, with `new`
+/// in every entry of history content, leaving other lines as they were,
+/// and returns the new content and the number of lines changed
+pub fn rename(content: &str, old: &str, new: &str, trim: bool) -> Result<(String, usize)> {
+    let old = normalize(old);
+    let mut changed = 0;
+    let mut lines = vec![];
+    for (line, ending) in split_lines(content) {
+        let renamed = match is_entry(line) {
+            true => rename_line(line, &old, new, trim)?,
+            false => None,
+        };
+        if renamed.is_some() {
+            changed += 1;
+        }
+        lines.push((renamed.unwrap_or_else(|| line.to_string()), ending));
+    }
+    Ok((join_lines(&lines), changed))
+}
+
+/// Returns an entry's line with the normalized name `old` replaced by `new`,
+/// or `None` if the entry doesn't have the name
+fn rename_line(line: &str, old: &str, new: &str, trim: bool) -> Result<Option<String>> {
+    let (field, rest) = split_field(line, trim)?;
+    let entry = Entry::parse(rest, false)?;
+    if !entry.names().iter().any(|name| normalize(name) == old) {
+        return Ok(None);
+    }
+    let entry = entry.map_names(|name| match normalize(name) == old {
+        true => new.to_string(),
+        false => name.to_string(),
+    });
+    Ok(Some(format!("{field}{entry}")))
+}
+
+/// Whether a line is a history entry rather than a comment or blank
+fn is_entry(line: &str) -> bool {
+    !line.trim().is_empty() && !line.starts_with('#')
+}
+
+/// Splits content into lines, each with its line ending, if any
+fn split_lines(content: &str) -> Vec<(&str, &str)> {
+    content
+        .split_inclusive('\n')
+        .map(|line| {
+            let text = line.trim_end_matches(['\n', '\r']);
+            (text, &line[text.len()..])
+        })
+        .collect()
+}
+
+fn join_lines(lines: &[(impl AsRef<str>, &str)]) -> String {
+    lines
+        .iter()
+        .map(|(line, ending)| format!("{}{ending}", line.as_ref()))
+        .collect()
+}
+
+fn last_entry(lines: &[(&str, &str)]) -> Result<usize> {
+    lines
+        .iter()
+        .rposition(|(line, _)| is_entry(line))
+        .ok_or_else(|| anyhow!("the history has no entries"))
+}
+
+/// Splits a line into its leading field, with the whitespace after it,
+/// and the rest, if `trim` is set
+fn split_field(line: &str, trim: bool) -> Result<(&str, &str)> {
+    if !trim {
+        return Ok(("", line));
+    }
+    match LEADING_FIELD.find(line) {
+        None => bail!("cannot trim history from line: {line}"),
+        Some(field) => Ok(line.split_at(field.end())),
+    }
+}
+
+/// Opens a file for appending, waiting for an exclusive lock on it,
+/// which is released when the file is closed.
+/// If an edit replaced the file while waiting, the new file is opened instead.
+fn open_locked(path: &Path) -> Result<File> {
+    loop {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .with_context(|| format!("opening {path:?}"))?;
+        file.lock().with_context(|| format!("locking {path:?}"))?;
+        if is_at(&file, path)? {
+            return Ok(file);
+        }
+    }
+}
+
+/// Whether an open file is still the one at `path`
+#[cfg(unix)]
+fn is_at(file: &File, path: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (open, at) = match (file.metadata(), fs::metadata(path)) {
+        (Ok(open), Ok(at)) => (open, at),
+        (_, Err(e)) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        (Err(e), _) | (_, Err(e)) => {
+            return Err(e).with_context(|| format!("reading metadata of {path:?}"))
+        }
+    };
+    Ok((open.dev(), open.ino()) == (at.dev(), at.ino()))
+}
+
+/// Whether an open file is still the one at `path`,
+/// which it always is where open files can't be replaced
+#[cfg(not(unix))]
+fn is_at(_file: &File, _path: &Path) -> Result<bool> {
+    Ok(true)
+}
+
+/// Writes lines after `current` content in one write,
+/// first ending any unterminated last line, and returns what was written
+fn write_lines(file: &mut File, path: &Path, current: &str, lines: &[String]) -> Result<String> {
+    let mut text = String::new();
+    if !current.is_empty() && !current.ends_with('\n') {
+        text.push('\n');
+    }
+    for line in lines {
+        text.push_str(line);
+        text.push('\n');
+    }
+    file.write_all(text.as_bytes())
+        .and_then(|()| file.sync_data())
+        .with_context(|| format!("writing {path:?}"))?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_append() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("nextspeaker-record-{}", std::process::id()));
+        fs::write(&path, "a\nb")?;
+        let mut file = HistoryFile::read(&path)?;
+        let mut other = file.clone();
+        file.append(&["c".to_string()])?;
+        assert_eq!(fs::read_to_string(&path)?, "a\nb\nc\n");
+        assert_eq!(file.content(), "a\nb\nc\n");
+        file.append(&["d".to_string(), "e".to_string()])?;
+        // Someone else's choice was made from the history before these were added.
+        assert!(other.append(&["f".to_string()]).is_err());
+        append(&path, &["f".to_string()])?;
+        assert_eq!(fs::read_to_string(&path)?, "a\nb\nc\nd\ne\nf\n");
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_file() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("nextspeaker-edit-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("history.txt");
+        fs::write(&path, "a\nb\n")?;
+        edit(&path, |content| Ok(content.replace('b', "c")))?;
+        assert_eq!(fs::read_to_string(&path)?, "a\nc\n");
+        // A failed edit leaves the history alone.
+        assert!(edit(&path, |_| bail!("no")).is_err());
+        assert_eq!(fs::read_to_string(&path)?, "a\nc\n");
+        // Only the history is left in its directory.
+        assert_eq!(fs::read_dir(&dir)?.count(), 1);
+        // A file opened before the edit replaced it is no longer the history.
+        let stale = File::open(&path)?;
+        edit(&path, |content| Ok(format!("{content}d\n")))?;
+        #[cfg(unix)]
+        assert!(!is_at(&stale, &path)?);
+        append(&path, &["e".to_string()])?;
+        assert_eq!(fs::read_to_string(&path)?, "a\nc\nd\ne\n");
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_edits() -> Result<()> {
+        let content = "# team history\n\
+            2026-10-01 Bob\n\
+            \n\
+            2026-10-02T09:00:00Z Alice > bob > Carl\n\
+            2026-10-03T09:00:00Z Bob [role=notes] [declined]\n\
+            # the end\n";
+        let (undone, removed) = undo(content, None)?;
+        assert_eq!(removed, "2026-10-03T09:00:00Z Bob [role=notes] [declined]");
+        assert_eq!(
+            undone,
+            "# team history\n2026-10-01 Bob\n\n2026-10-02T09:00:00Z Alice > bob > Carl\n# the end\n"
+        );
+        let since = |day| DateTime::parse_from_rfc3339(day).map(|t| t.to_utc());
+        assert!(undo(content, Some(since("2026-10-03T08:00:00Z")?)).is_ok());
+        assert!(undo(content, Some(since("2026-10-03T10:00:00Z")?)).is_err());
+        assert!(undo("# nothing\n", None).is_err());
+
+        let (amended, replaced) = amend(content, "Dee", true)?;
+        assert_eq!(replaced, "2026-10-03T09:00:00Z Bob [role=notes] [declined]");
+        assert!(amended.ends_with("2026-10-03T09:00:00Z Dee [role=notes] [declined]\n# the end\n"));
+        assert!(amend("Alice + Bob; Carl + Dee [grouping]\n", "Eve", false).is_err());
+
+        let (renamed, changed) = rename(content, "BOB", "Robert", true)?;
+        assert_eq!(changed, 3);
+        assert_eq!(
+            renamed,
+            "# team history\n\
+            2026-10-01 Robert\n\
+            \n\
+            2026-10-02T09:00:00Z Alice > Robert > Carl\n\
+            2026-10-03T09:00:00Z Robert [role=notes] [declined]\n\
+            # the end\n"
+        );
+        // Lines that don't mention the name are left exactly as they were.
+        let (renamed, changed) = rename("Alice  \nBob", "Bob", "Robert", false)?;
+        assert_eq!((renamed.as_str(), changed), ("Alice  \nRobert", 1));
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+//! Participants and the other names they go by
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use log::warn;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::history::Entry;
+
+/// Separates a participant's name from their aliases, e.g., "Robert | Bob"
+pub const ALIAS_SEPARATOR: &str = "|";
+
+/// Puts a name in the form used for matching:
+/// Unicode compatibility normalization, single spaces, and lower case
+pub fn normalize(name: &str) -> String {
+    // ASCII is already in normal form, and lower-casing it is simpler.
+    if name.is_ascii() {
+        return name
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_ascii_lowercase();
+    }
+    name.nfkc()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// The participants, each known by the first name on their line
+/// and matched by any of the names on it
+#[derive(Clone, Debug, Default)]
+pub struct Roster {
+    participants: Vec<String>,
+    /// Participants' indexes by their normalized names and aliases
+    names: HashMap<String, usize>,
+}
+
+impl Roster {
+    /// Reads a line per participant, listing their name and then any aliases,
+    /// separated by "|".
+    /// A participant listed twice is only counted once.
+    pub fn parse(lines: &[String]) -> Result<Self> {
+        let mut roster = Self::default();
+        for line in lines {
+            roster.push_line(line)?;
+        }
+        Ok(roster)
+    }
+
+    /// Adds the participant on a line like those `parse` reads,
+    /// leaving the roster as it was if any of their names is another's
+    pub fn push_line(&mut self, line: &str) -> Result<()> {
+        let names = line
+            .split(ALIAS_SEPARATOR)
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+        let Some(name) = names.first() else {
+            return Ok(());
+        };
+        if let Some(existing) = self.canonical(name) {
+            warn!("{name} is listed more than once as {existing}");
+            return Ok(());
+        }
+        for alias in &names {
+            if let Some(other) = self.canonical(alias) {
+                bail!("{alias} could be {other} or {name}");
+            }
+        }
+        let i = self.participants.len();
+        self.participants.push(name.to_string());
+        for alias in names {
+            self.names.insert(normalize(alias), i);
+        }
+        Ok(())
+    }
+
+    pub fn participants(&self) -> &[String] {
+        &self.participants
+    }
+
+    /// Returns the participant going by `name`, if any
+    pub fn canonical(&self, name: &str) -> Option<&str> {
+        self.names
+            .get(&normalize(name))
+            .map(|&i| self.participants[i].as_str())
+    }
+
+    /// Returns each normalized name or alias with the participant it belongs to
+    pub fn aliases(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.names
+            .iter()
+            .map(|(alias, &i)| (alias.as_str(), self.participants[i].as_str()))
+    }
+
+    /// Returns the participant going by `name`, or the name unchanged
+    pub fn canonical_or_same(&self, name: &str) -> String {
+        self.canonical(name).unwrap_or(name).to_string()
+    }
+
+    /// Rewrites the names in history entries as the participants they match,
+    /// returning the names that match no participant,
+    /// each once, in the order they first appear
+    pub fn canonicalize(&self, history: &mut [Entry]) -> Vec<String> {
+        let mut unmatched: Vec<String> = vec![];
+        for entry in history.iter_mut() {
+            *entry = entry.map_names(|name| match self.canonical(name) {
+                Some(participant) => participant.to_string(),
+                None => {
+                    if !unmatched.iter().any(|u| u == name) {
+                        unmatched.push(name.to_string());
+                    }
+                    name.to_string()
+                }
+            });
+        }
+        unmatched
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::history;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("  Mary \t Ann "), "mary ann");
+        // A precomposed "é" and an "e" with a combining accent
+        assert_eq!(normalize("Ren\u{e9}e"), normalize("Rene\u{301}e"));
+        assert_eq!(normalize("ＢＯＢ"), "bob");
+    }
+
+    #[test]
+    fn test_roster() -> Result<()> {
+        let lines = ["Robert | Bob | bob", "Alice", "alice ", "Carl|"].map(String::from);
+        let roster = Roster::parse(&lines)?;
+        assert_eq!(roster.participants(), ["Robert", "Alice", "Carl"]);
+        assert_eq!(roster.canonical("BOB"), Some("Robert"));
+        assert_eq!(roster.canonical("Bobby"), None);
+        let mut history = history::parse(
+            &[
+                "bob",
+                "ALICE > Bobby > carl",
+                "Bob + Alice; Dee + carl [grouping]",
+            ]
+            .map(String::from),
+            false,
+        )?;
+        let unmatched = roster.canonicalize(&mut history);
+        assert_eq!(unmatched, ["Bobby", "Dee"]);
+        let texts = history.iter().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                "Robert",
+                "Alice > Bobby > Carl",
+                "Robert + Alice; Dee + Carl"
+            ]
+        );
+        assert!(Roster::parse(&["Robert | Bob", "Roberta | Bob"].map(String::from)).is_err());
+        let mut roster = Roster::parse(&["Robert | Bob".to_string()])?;
+        assert!(roster.push_line("Roberta | Bobbie | Bob").is_err());
+        assert_eq!(roster.participants(), ["Robert"]);
+        assert_eq!(roster.canonical("Bobbie"), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+//! Simulating many future meetings to see how fair choices are in the long run
+
+use anyhow::{bail, Result};
+use rand::Rng;
+
+use crate::{
+    choose_from_index,
+    history::{Entry, HistoryIndex},
+    Decay, MaxGap, Options,
+};
+
+/// Simulates `runs` independent series of `meetings` choices,
+/// each choice joining the history before the next,
+/// and returns the participants chosen in each run.
+/// Decay by days isn't supported, since simulated meetings have no dates.
+pub fn simulate_meetings(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+    meetings: usize,
+    runs: usize,
+) -> Result<Vec<Vec<String>>> {
+    simulate_meetings_with_rng(
+        participants,
+        history,
+        options,
+        meetings,
+        runs,
+        &mut rand::thread_rng(),
+    )
+}
+
+pub fn simulate_meetings_with_rng<R: Rng + ?Sized>(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+    meetings: usize,
+    runs: usize,
+    rng: &mut R,
+) -> Result<Vec<Vec<String>>> {
+    if let Decay::Days { .. } = options.decay {
+        bail!("simulated meetings have no dates, so they need decay by selections");
+    }
+    if let Some(MaxGap::Days { .. }) = options.max_gap {
+        bail!("simulated meetings have no dates, so they need a maximum gap in entries");
+    }
+    if runs == 0 || meetings == 0 {
+        bail!("simulating needs at least one run of at least one meeting");
+    }
+    let start = HistoryIndex::new(history, options)?;
+    (0..runs)
+        .map(|_| {
+            let mut index = start.clone();
+            let mut chosen = Vec::with_capacity(meetings);
+            for _ in 0..meetings {
+                let selection = choose_from_index(participants, &index, options, rng)?.chosen;
+                index.push(&Entry::new(&selection))?;
+                chosen.push(selection);
+            }
+            Ok(chosen)
+        })
+        .collect()
+}
+
+/// How long a newcomer took to catch up with everyone else
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parity {
+    /// The mean number of meetings to reach parity, in runs where it was reached
+    pub mean_meetings: Option<f64>,
+    /// The fraction of runs in which parity was reached
+    pub reached: f64,
+}
+
+/// One participant's experience over the simulated runs
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParticipantSummary {
+    pub name: String,
+    /// Turns per run, on average
+    pub mean_turns: f64,
+    /// The median number of meetings from one turn to the next
+    pub median_gap: Option<usize>,
+    /// The 90th percentile of the number of meetings from one turn to the next
+    pub p90_gap: Option<usize>,
+    /// The longest run of meetings without a turn, averaged over runs
+    pub mean_longest_wait: f64,
+    /// The longest run of meetings without a turn in any run
+    pub longest_wait: usize,
+    /// For participants who have never been chosen,
+    /// when their decayed tally caught up with the mean of everyone else's
+    pub parity: Option<Parity>,
+}
+
+/// Per-participant statistics of simulated runs
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub meetings: usize,
+    pub runs: usize,
+    pub participants: Vec<ParticipantSummary>,
+}
+
+impl Summary {
+    /// Summarizes the runs from `simulate_meetings` with the same arguments
+    pub fn new(
+        participants: &[String],
+        history: &[Entry],
+        options: &Options,
+        runs: &[Vec<String>],
+    ) -> Result<Self> {
+        let Some(meetings) = runs.first().map(Vec::len) else {
+            bail!("summarizing needs at least one run");
+        };
+        let start = HistoryIndex::new(history, options)?;
+        let is_newcomer = start
+            .tallies(participants)
+            .iter()
+            .map(|tally| tally.history_weight == 0.0 && tally.since_last.is_none())
+            .collect::<Vec<_>>();
+        let any_established = is_newcomer.iter().any(|newcomer| !newcomer);
+        let mut parity_at = vec![vec![]; participants.len()];
+        if any_established {
+            for run in runs {
+                let mut index = start.clone();
+                let mut reached = vec![None; participants.len()];
+                for (m, selection) in run.iter().enumerate() {
+                    index.push(&Entry::new(selection))?;
+                    let tallies = index.tallies(participants);
+                    let (total, count) = tallies
+                        .iter()
+                        .zip(&is_newcomer)
+                        .filter(|(_, newcomer)| !**newcomer)
+                        .fold((0.0, 0), |(t, n), (tally, _)| {
+                            (t + tally.history_weight, n + 1)
+                        });
+                    let mean = total / count as f64;
+                    for (i, tally) in tallies.iter().enumerate() {
+                        if is_newcomer[i] && reached[i].is_none() && tally.history_weight >= mean {
+                            reached[i] = Some(m + 1);
+                        }
+                    }
+                }
+                for (i, meeting) in reached.into_iter().enumerate() {
+                    parity_at[i].push(meeting);
+                }
+            }
+        }
+        let summaries = participants
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let mut gaps = vec![];
+                let mut turns = 0;
+                let mut longest_waits = vec![];
+                for run in runs {
+                    let picks = run
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, chosen)| *chosen == name)
+                        .map(|(m, _)| m)
+                        .collect::<Vec<_>>();
+                    turns += picks.len();
+                    gaps.extend(picks.windows(2).map(|pair| pair[1] - pair[0]));
+                    longest_waits.push(longest_wait(&picks, run.len()));
+                }
+                gaps.sort_unstable();
+                let parity = (any_established && is_newcomer[i]).then(|| {
+                    let reached = parity_at[i].iter().flatten().collect::<Vec<_>>();
+                    Parity {
+                        mean_meetings: (!reached.is_empty()).then(|| {
+                            reached.iter().map(|m| **m as f64).sum::<f64>() / reached.len() as f64
+                        }),
+                        reached: reached.len() as f64 / runs.len() as f64,
+                    }
+                });
+                ParticipantSummary {
+                    name: name.to_string(),
+                    mean_turns: turns as f64 / runs.len() as f64,
+                    median_gap: percentile(&gaps, 0.5),
+                    p90_gap: percentile(&gaps, 0.9),
+                    mean_longest_wait: longest_waits.iter().sum::<usize>() as f64
+                        / runs.len() as f64,
+                    longest_wait: longest_waits.into_iter().max().unwrap_or(0),
+                    parity,
+                }
+            })
+            .collect();
+        Ok(Self {
+            meetings,
+            runs: runs.len(),
+            participants: summaries,
+        })
+    }
+}
+
+/// The longest run of meetings without a turn,
+/// given the meetings with turns, in order, out of `meetings`
+pub fn longest_wait(turns: &[usize], meetings: usize) -> usize {
+    let mut longest = 0;
+    let mut next_free = 0;
+    for &turn in turns {
+        longest = longest.max(turn - next_free);
+        next_free = turn + 1;
+    }
+    longest.max(meetings - next_free)
+}
+
+/// The nearest-rank percentile of sorted values
+fn percentile(sorted: &[usize], fraction: f64) -> Option<usize> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (fraction * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::strategy::StrategyName;
+
+    #[test]
+    fn test_longest_wait() {
+        assert_eq!(longest_wait(&[], 5), 5);
+        assert_eq!(longest_wait(&[0, 4], 5), 3);
+        assert_eq!(longest_wait(&[2], 5), 2);
+        assert_eq!(longest_wait(&[1, 2], 6), 3);
+        assert_eq!(percentile(&[1, 2, 3, 4], 0.5), Some(2));
+        assert_eq!(percentile(&[1, 2, 3, 4], 0.9), Some(4));
+    }
+
+    #[test]
+    fn test_simulate() -> Result<()> {
+        let participants = "abcd".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let history = ["a", "b", "c", "a", "b", "c"].map(Entry::new);
+        let options = Options {
+            strategy: StrategyName::RoundRobin.strategy(),
+            ..Options::with_halflife(1.0)
+        };
+        let rng = &mut StdRng::seed_from_u64(1);
+        let runs = simulate_meetings_with_rng(&participants, &history, &options, 8, 3, rng)?;
+        assert_eq!(runs.len(), 3);
+        // Round robin takes the newcomer first, then everyone in turn.
+        for run in &runs {
+            assert_eq!(run[0], "d");
+            assert_eq!(run[1..4], ["a", "b", "c"]);
+        }
+        let summary = Summary::new(&participants, &history, &options, &runs)?;
+        let d = &summary.participants[3];
+        assert_eq!(d.mean_turns, 2.0);
+        assert_eq!(d.median_gap, Some(4));
+        assert_eq!(d.longest_wait, 3);
+        let parity = d.parity.as_ref().unwrap();
+        assert_eq!(parity.reached, 1.0);
+        assert_eq!(parity.mean_meetings, Some(1.0));
+        assert!(summary.participants[0].parity.is_none());
+        let days = Options {
+            decay: Decay::Days {
+                halflife: 7.0,
+                now: chrono::Utc::now(),
+            },
+            ..Default::default()
+        };
+        assert!(simulate_meetings(&participants, &[], &days, 1, 1).is_err());
+        let options = Options::default();
+        assert!(simulate_meetings(&participants, &[], &options, 8, 0).is_err());
+        assert!(simulate_meetings(&participants, &[], &options, 0, 3).is_err());
+        assert!(Summary::new(&participants, &[], &options, &[]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! Ways of weighting participants for selection
+
+use std::{fmt::Debug, sync::Arc};
+
+use clap::ValueEnum;
+
+/// The lowest decayed count of turns that strategies weigh,
+/// so that declines counted as debt keep weights positive and finite
+pub const MIN_TURNS: f64 = -0.9;
+
+/// A participant's standing in the history
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tally {
+    /// Decayed count of the participant's previous turns,
+    /// which declines counted as debt can make negative
+    pub history_weight: f64,
+    /// The number of history entries since the participant last led one,
+    /// counting only turns that were taken, or `None` if they never have
+    pub since_last: Option<usize>,
+}
+
+/// The distribution a candidate's selection weight is drawn from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Weight {
+    Fixed(f64),
+    Beta { alpha: f64, beta: f64 },
+}
+
+pub trait SelectionStrategy: Debug + Send + Sync {
+    /// Returns the weight distribution for each participant's tally
+    fn weights(&self, tallies: &[Tally]) -> Vec<Weight>;
+
+    /// Whether recently chosen participants are excluded from selection
+    fn excludes_recent(&self) -> bool {
+        true
+    }
+}
+
+/// Weights drawn from a Beta distribution that leans toward zero
+/// the more a participant has been chosen, with decay
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BetaDecayStrategy;
+
+impl SelectionStrategy for BetaDecayStrategy {
+    fn weights(&self, tallies: &[Tally]) -> Vec<Weight> {
+        tallies
+            .iter()
+            .map(|tally| Weight::Beta {
+                alpha: 1.0,
+                beta: 1.0 + tally.history_weight.max(MIN_TURNS),
+            })
+            .collect()
+    }
+}
+
+/// Strictly the least recently chosen, with ties broken at random
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RoundRobinStrategy;
+
+impl SelectionStrategy for RoundRobinStrategy {
+    fn weights(&self, tallies: &[Tally]) -> Vec<Weight> {
+        // Never having been chosen sorts after any number of entries.
+        let wait = |tally: &Tally| tally.since_last.unwrap_or(usize::MAX);
+        let longest = tallies.iter().map(wait).max().unwrap_or(0);
+        tallies
+            .iter()
+            .map(|tally| Weight::Fixed(if wait(tally) == longest { 1.0 } else { 0.0 }))
+            .collect()
+    }
+
+    fn excludes_recent(&self) -> bool {
+        false
+    }
+}
+
+/// Weights inversely proportional to one more than the decayed count of turns
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InverseCountStrategy;
+
+impl SelectionStrategy for InverseCountStrategy {
+    fn weights(&self, tallies: &[Tally]) -> Vec<Weight> {
+        tallies
+            .iter()
+            .map(|tally| Weight::Fixed(1.0 / (1.0 + tally.history_weight.max(MIN_TURNS))))
+            .collect()
+    }
+}
+
+/// Equal weights, ignoring history altogether
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UniformStrategy;
+
+impl SelectionStrategy for UniformStrategy {
+    fn weights(&self, tallies: &[Tally]) -> Vec<Weight> {
+        vec![Weight::Fixed(1.0); tallies.len()]
+    }
+
+    fn excludes_recent(&self) -> bool {
+        false
+    }
+}
+
+/// The strategies available on the command line
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum StrategyName {
+    BetaDecay,
+    RoundRobin,
+    InverseCount,
+    Uniform,
+}
+
+impl StrategyName {
+    pub fn strategy(self) -> Arc<dyn SelectionStrategy> {
+        match self {
+            Self::BetaDecay => Arc::new(BetaDecayStrategy),
+            Self::RoundRobin => Arc::new(RoundRobinStrategy),
+            Self::InverseCount => Arc::new(InverseCountStrategy),
+            Self::Uniform => Arc::new(UniformStrategy),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_robin() {
+        let tally = |since_last| Tally {
+            history_weight: 1.0,
+            since_last,
+        };
+        let weights = RoundRobinStrategy.weights(&[tally(Some(3)), tally(Some(5)), tally(Some(5))]);
+        assert_eq!(
+            weights,
+            [Weight::Fixed(0.0), Weight::Fixed(1.0), Weight::Fixed(1.0)]
+        );
+        let weights = RoundRobinStrategy.weights(&[tally(Some(9)), tally(None)]);
+        assert_eq!(weights, [Weight::Fixed(0.0), Weight::Fixed(1.0)]);
+    }
+}
@@ -0,0 +1,233 @@
+//! Searching for settings that meet a fairness target by simulation
+
+use anyhow::{bail, Result};
+use rand::Rng;
+
+use crate::{
+    history::{Entry, HistoryIndex},
+    simulate::{longest_wait, simulate_meetings_with_rng},
+    Decay, Options, RecentWindow,
+};
+
+/// The half lives tried, in selections
+pub const HALFLIVES: [f64; 8] = [1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 50.0, 100.0];
+/// The shares of participants tried as fixed recent windows
+pub const RECENT_SHARES: [f64; 3] = [0.25, 0.5, 0.75];
+/// The share of runs in which the longest wait must meet the target
+pub const QUANTILE: f64 = 0.95;
+
+/// How one setting fared in simulation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trial {
+    pub halflife: f64,
+    pub recent_window: RecentWindow,
+    /// The longest wait in the worst run but for the worst 5%,
+    /// where a run's longest wait is anyone's longest stretch of meetings without a turn
+    pub longest_wait: usize,
+    /// The share of choices that weren't simply whoever had waited longest
+    pub unpredictability: f64,
+    pub meets_target: bool,
+}
+
+/// The settings tried, and which was best
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    pub trials: Vec<Trial>,
+    /// The most unpredictable trial that meets the target,
+    /// or if none does, the one with the shortest longest wait
+    pub best: usize,
+}
+
+/// Returns the recent windows tried for a number of participants:
+/// none, automatic, and one entry or some share of the participants
+pub fn recent_windows(n_participants: usize) -> Vec<RecentWindow> {
+    let mut windows = vec![RecentWindow::None, RecentWindow::Auto];
+    let sizes = RECENT_SHARES.map(|share| (share * n_participants as f64) as usize);
+    for size in [1].into_iter().chain(sizes) {
+        let window = RecentWindow::Entries(size);
+        if size >= 1 && size < n_participants && !windows.contains(&window) {
+            windows.push(window);
+        }
+    }
+    windows
+}
+
+/// Simulates each combination of `HALFLIVES` and `recent_windows`
+/// with the rest of `options`, looking for the most unpredictable setting
+/// in which no one waits longer than `max_wait` meetings in most runs
+pub fn tune_with_rng<R: Rng + ?Sized>(
+    participants: &[String],
+    history: &[Entry],
+    options: &Options,
+    max_wait: usize,
+    meetings: usize,
+    runs: usize,
+    rng: &mut R,
+) -> Result<Tuning> {
+    if let Decay::Days { .. } = options.decay {
+        bail!("tuning simulates meetings, which have no dates, so it needs decay by selections");
+    }
+    if runs == 0 {
+        bail!("tuning needs at least one run");
+    }
+    let mut trials = vec![];
+    for halflife in HALFLIVES {
+        for recent_window in recent_windows(participants.len()) {
+            let options = Options {
+                decay: Decay::Selections(halflife),
+                recent_window,
+                ..options.clone()
+            };
+            let simulated =
+                simulate_meetings_with_rng(participants, history, &options, meetings, runs, rng)?;
+            let index = HistoryIndex::new(history, &options)?;
+            let waits = index
+                .tallies(participants)
+                .iter()
+                .map(|tally| tally.since_last)
+                .collect::<Vec<_>>();
+            let mut longest_waits = simulated
+                .iter()
+                .map(|run| {
+                    participants
+                        .iter()
+                        .map(|name| {
+                            let turns = run
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, chosen)| *chosen == name)
+                                .map(|(m, _)| m)
+                                .collect::<Vec<_>>();
+                            longest_wait(&turns, run.len())
+                        })
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect::<Vec<_>>();
+            longest_waits.sort_unstable();
+            let rank = ((QUANTILE * runs as f64).ceil() as usize).clamp(1, runs);
+            let longest_wait = longest_waits[rank - 1];
+            let unpredictability = simulated
+                .iter()
+                .map(|run| unpredictability(participants, &waits, run))
+                .sum::<f64>()
+                / runs as f64;
+            trials.push(Trial {
+                halflife,
+                recent_window,
+                longest_wait,
+                unpredictability,
+                meets_target: longest_wait <= max_wait,
+            });
+        }
+    }
+    let best = trials
+        .iter()
+        .enumerate()
+        .filter(|(_, trial)| trial.meets_target)
+        .max_by(|(_, a), (_, b)| a.unpredictability.total_cmp(&b.unpredictability))
+        .or_else(|| {
+            trials
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, trial)| trial.longest_wait)
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    Ok(Tuning { trials, best })
+}
+
+/// Returns the share of choices in a run that weren't whoever had waited longest,
+/// counting a choice among several tied for the longest wait as partly predictable.
+/// `waits` gives how many entries each participant has waited at the start,
+/// with `None` for never having been chosen.
+pub fn unpredictability(participants: &[String], waits: &[Option<usize>], run: &[String]) -> f64 {
+    if run.is_empty() || participants.is_empty() {
+        return 0.0;
+    }
+    // Waits are compared as the meeting of each participant's last turn,
+    // where `None`, for never having a turn, sorts first.
+    let mut last_turn = waits
+        .iter()
+        .map(|wait| wait.map(|w| -(w as i64)))
+        .collect::<Vec<_>>();
+    let mut predictable = 0.0;
+    for (m, chosen) in run.iter().enumerate() {
+        let earliest = last_turn.iter().min().copied().unwrap_or_default();
+        let longest_waiting = participants
+            .iter()
+            .zip(&last_turn)
+            .filter(|(_, last)| **last == earliest)
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        if longest_waiting.contains(&chosen) {
+            predictable += 1.0 / longest_waiting.len() as f64;
+        }
+        if let Some(i) = participants.iter().position(|name| name == chosen) {
+            last_turn[i] = Some(m as i64 + 1);
+        }
+    }
+    1.0 - predictable / run.len() as f64
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::strategy::StrategyName;
+
+    #[test]
+    fn test_unpredictability() {
+        let participants = ["a", "b", "c"].map(String::from);
+        let waits = [Some(0), Some(1), Some(2)];
+        let round_robin = ["c", "b", "a", "c"].map(String::from);
+        assert_eq!(unpredictability(&participants, &waits, &round_robin), 0.0);
+        let repeats = ["a", "a"].map(String::from);
+        assert_eq!(unpredictability(&participants, &waits, &repeats), 1.0);
+        // With no history, each pick is one of three tied.
+        let fresh = ["b"].map(String::from);
+        let never = [None, None, None];
+        let expected = 1.0 - 1.0 / 3.0;
+        assert!((unpredictability(&participants, &never, &fresh) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_recent_windows() {
+        use RecentWindow::*;
+        assert_eq!(recent_windows(1), [None, Auto]);
+        assert_eq!(
+            recent_windows(6),
+            [None, Auto, Entries(1), Entries(3), Entries(4)]
+        );
+    }
+
+    #[test]
+    fn test_tune() -> Result<()> {
+        let participants = "abcdef".chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        let rng = &mut StdRng::seed_from_u64(1);
+        let tuning = tune_with_rng(&participants, &[], &Options::default(), 12, 24, 20, rng)?;
+        assert_eq!(
+            tuning.trials.len(),
+            HALFLIVES.len() * recent_windows(6).len()
+        );
+        let best = &tuning.trials[tuning.best];
+        assert!(best.meets_target);
+        assert!(tuning
+            .trials
+            .iter()
+            .filter(|trial| trial.meets_target)
+            .all(|trial| trial.unpredictability <= best.unpredictability));
+        // Round robin never waits long, but is entirely predictable.
+        let options = Options {
+            strategy: StrategyName::RoundRobin.strategy(),
+            ..Default::default()
+        };
+        let tuning = tune_with_rng(&participants, &[], &options, 12, 24, 5, rng)?;
+        assert!(tuning
+            .trials
+            .iter()
+            .all(|trial| trial.meets_target && trial.unpredictability < 0.2));
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+//! Checks of the participant list and history for likely mistakes
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    history::Entry,
+    roster::{self, Roster, ALIAS_SEPARATOR},
+};
+
+/// The largest edit distance at which a name counts as a near miss
+const MAX_TYPO_DISTANCE: usize = 2;
+
+/// A likely mistake, at a line number counted from one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// A participant name or alias on more than one line
+    Duplicate {
+        line: usize,
+        name: String,
+        first_line: usize,
+    },
+    /// A history name that matches no participant,
+    /// with the participant it most nearly matches, if any is close
+    UnknownName {
+        line: usize,
+        name: String,
+        suggestion: Option<String>,
+    },
+    /// A participant line that cannot be read,
+    /// e.g., because one of its names could be another participant
+    BadParticipant { line: usize, message: String },
+    /// A history line that cannot be read, e.g., because it can't be trimmed
+    Unparsable { line: usize, message: String },
+}
+
+impl Problem {
+    pub fn line(&self) -> usize {
+        match self {
+            Self::Duplicate { line, .. }
+            | Self::UnknownName { line, .. }
+            | Self::BadParticipant { line, .. }
+            | Self::Unparsable { line, .. } => *line,
+        }
+    }
+
+    /// Whether the problem is in the participant list rather than the history
+    pub fn in_participants(&self) -> bool {
+        matches!(self, Self::Duplicate { .. } | Self::BadParticipant { .. })
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate {
+                name, first_line, ..
+            } => write!(f, "{name} is already listed on line {first_line}"),
+            Self::UnknownName {
+                name,
+                suggestion: Some(suggestion),
+                ..
+            } => write!(f, "{name} is not a participant; did you mean {suggestion}?"),
+            Self::UnknownName { name, .. } => write!(f, "{name} is not a participant"),
+            Self::BadParticipant { message, .. } | Self::Unparsable { message, .. } => {
+                write!(f, "{message}")
+            }
+        }
+    }
+}
+
+/// Returns the lines that count, with their line numbers,
+/// skipping blank lines and comments as the participant and history files do
+pub fn numbered_lines(content: &str) -> Vec<(usize, &str)> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| (i + 1, line))
+        .collect()
+}
+
+/// Checks the contents of a participant list and a history,
+/// returning problems in the participant list first, then in the history,
+/// each in line order
+pub fn validate(participants: &str, history: &str, trim: bool) -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut seen = HashMap::new();
+    // Lines that can't be added are reported, and the history is checked against the rest.
+    let mut roster = Roster::default();
+    for (line, text) in numbered_lines(participants) {
+        for name in text.split(ALIAS_SEPARATOR).map(str::trim) {
+            if name.is_empty() {
+                continue;
+            }
+            match seen.get(&roster::normalize(name)) {
+                Some(&first_line) if first_line != line => problems.push(Problem::Duplicate {
+                    line,
+                    name: name.to_string(),
+                    first_line,
+                }),
+                Some(_) => {}
+                None => {
+                    seen.insert(roster::normalize(name), line);
+                }
+            }
+        }
+        if problems.iter().any(|p| p.line() == line) {
+            continue;
+        }
+        if let Err(e) = roster.push_line(text) {
+            problems.push(Problem::BadParticipant {
+                line,
+                message: e.to_string(),
+            });
+        }
+    }
+    for (line, text) in numbered_lines(history) {
+        let entry = match Entry::parse(text, trim) {
+            Ok(entry) => entry,
+            Err(e) => {
+                problems.push(Problem::Unparsable {
+                    line,
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+        for name in entry.names() {
+            if roster.canonical(name).is_none() {
+                problems.push(Problem::UnknownName {
+                    line,
+                    name: name.to_string(),
+                    suggestion: nearest(&roster, name),
+                });
+            }
+        }
+    }
+    problems
+}
+
+/// Returns the participant whose name or alias is within a few edits of `name`
+fn nearest(roster: &Roster, name: &str) -> Option<String> {
+    let name = roster::normalize(name);
+    roster
+        .aliases()
+        .map(|(alias, participant)| (strsim::levenshtein(&name, alias), participant))
+        .filter(|(distance, _)| *distance <= MAX_TYPO_DISTANCE && *distance < name.chars().count())
+        .min()
+        .map(|(_, participant)| participant.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let participants = "Alice\nRobert | Bob\n\n# the new hire\nCarl\nbob\nalice\n";
+        let history = "1 Alice\n2 Bbo\n3 Zed > Carl\nMary\n4 Carl [nope]\n";
+        let problems = validate(participants, history, true);
+        let expected = [
+            Problem::Duplicate {
+                line: 6,
+                name: "bob".to_string(),
+                first_line: 2,
+            },
+            Problem::Duplicate {
+                line: 7,
+                name: "alice".to_string(),
+                first_line: 1,
+            },
+            Problem::UnknownName {
+                line: 2,
+                name: "Bbo".to_string(),
+                suggestion: Some("Robert".to_string()),
+            },
+            Problem::UnknownName {
+                line: 3,
+                name: "Zed".to_string(),
+                suggestion: None,
+            },
+        ];
+        assert_eq!(problems[..4], expected);
+        assert!(matches!(problems[4], Problem::Unparsable { line: 4, .. }));
+        // Brackets that aren't a tag are read as part of the name.
+        assert!(matches!(
+            &problems[5],
+            Problem::UnknownName { line: 5, name, .. } if name == "Carl [nope]"
+        ));
+        assert_eq!(problems.len(), 6);
+        assert!(validate("Alice\nBob\n", "Alice\nbob\n", false).is_empty());
+    }
+}